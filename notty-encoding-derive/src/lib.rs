@@ -0,0 +1,487 @@
+//! `#[derive(Argument)]`, which generates the encoding and decoding of a notty escape sequence
+//! argument from the definition of its type.
+//!
+//! A struct is encoded as its fields in order, separated by `.`. An enum is encoded as the tag
+//! of the variant, given by its `#[tag = N]` attribute, followed by the fields of the variant.
+//! Every field must itself implement `Argument`.
+//!
+//! Fields accept these attributes:
+//!
+//! * `#[default = value]`: the value of the field when it is absent. The value is either a
+//!   literal or a string containing an expression, such as `"Direction::Right"`. Fields with
+//!   defaults must follow all of the fields without them.
+//! * `#[name = "name"]`: the name of the field in decoding errors. Named fields default to their
+//!   own name, and tuple fields to `value`.
+//! * `#[max = N]`: the largest value the field may have; larger values are out of range.
+//! * `#[skip]`: the field is neither encoded nor decoded, and always holds its `Default` value.
+//!   It need not implement `Argument`.
+//!
+//! Two variants may share a tag if they are marked `#[flag = false]` and `#[flag = true]` and have
+//! the same fields. They are told apart by a boolean field after their other fields, which is only
+//! encoded for the `true` variant and decodes as `false` when it is absent.
+//!
+//! A variant with fields may also be given a `#[legacy_tag = N]` attribute, naming the tag of a
+//! unit variant which it used to share. It is always encoded with its own tag, but the legacy tag
+//! followed by fields still decodes as this variant; the legacy tag alone decodes as the unit
+//! variant. Fields which follow the legacy tag but cannot be decoded are never taken to mean the
+//! unit variant.
+//!
+//! A struct may be given a `#[new = "path"]` attribute, naming a function which takes its fields
+//! in order and returns `Option<Self>`; values for which it returns `None` are out of range.
+
+#![allow(clippy::redundant_field_names)]
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Literal, Span, TokenStream as Tokens};
+use syn::{Data, DeriveInput, Expr, Fields, Ident, Lit, LitStr, Meta, Variant};
+use syn::spanned::Spanned;
+
+#[proc_macro_derive(Argument, attributes(tag, flag, legacy_tag, new, default, name, max, skip))]
+pub fn derive_argument(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    match derive(&input) {
+        Ok(tokens)  => tokens.into(),
+        Err(err)    => err.to_compile_error().into(),
+    }
+}
+
+struct Field {
+    // How the field is bound in patterns and expressions.
+    binding: Ident,
+    ty: syn::Type,
+    name: LitStr,
+    default: Option<Expr>,
+    max: Option<Expr>,
+    skip: bool,
+}
+
+struct Shape {
+    fields: Vec<Field>,
+    named: bool,
+    unit: bool,
+}
+
+fn derive(input: &DeriveInput) -> syn::Result<Tokens> {
+    let ty = &input.ident;
+    let (from_nums, encode, read) = match input.data {
+        Data::Struct(ref data)  => {
+            let shape = shape(&data.fields)?;
+            let pattern = pattern(quote!(#ty), &shape);
+            let from_nums = from_nums_fields(&shape);
+            let read = read_fields(&shape);
+            let encode = encode_fields(&shape, false);
+            let (from_nums, read) = match attr(&input.attrs, "new")? {
+                Some(Expr::Lit(syn::ExprLit { lit: Lit::Str(path), .. }))  => {
+                    let new: syn::Path = path.parse()?;
+                    let bindings = bindings(&shape);
+                    (quote!({ #from_nums #new(#(#bindings),*).or(default) }),
+                     quote!({
+                         #read
+                         #new(#(#bindings),*).ok_or_else(|| {
+                             fields.error(::notty_encoding::ErrorKind::OutOfRange)
+                         })
+                     }))
+                }
+                Some(expr)  => return Err(syn::Error::new(expr.span(), "expected a string")),
+                None        => {
+                    let ctor = construct(quote!(#ty), &shape);
+                    (quote!({ #from_nums Some(#ctor) }), quote!({ #read Ok(#ctor) }))
+                }
+            };
+            (from_nums,
+             quote!(match *self { #pattern => { #encode Ok(()) } }),
+             read)
+        }
+        Data::Enum(ref data)    => derive_enum(ty, data.variants.iter().collect())?,
+        Data::Union(_)          => {
+            return Err(syn::Error::new(input.span(), "unions cannot derive Argument"))
+        }
+    };
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::notty_encoding::args::Argument for #ty #ty_generics #where_clause {
+            #[allow(unused_mut)]
+            fn from_nums<I>(mut args: I, default: Option<Self>) -> Option<Self>
+            where I: Iterator<Item=u64> {
+                #from_nums
+            }
+
+            fn encode_to<W>(&self, w: &mut W) -> ::std::io::Result<()>
+            where W: ::std::io::Write + ?Sized {
+                #encode
+            }
+
+            #[allow(unused_variables)]
+            fn read(fields: &mut ::notty_encoding::args::Fields)
+                    -> Result<Self, ::notty_encoding::DecodeError> {
+                #read
+            }
+        }
+    })
+}
+
+fn derive_enum(ty: &Ident, variants: Vec<&Variant>) -> syn::Result<(Tokens, Tokens, Tokens)> {
+    let mut tagged = Vec::new();
+    for variant in variants {
+        let tag = match attr(&variant.attrs, "tag")? {
+            Some(Expr::Lit(syn::ExprLit { lit: Lit::Int(ref n), .. })) => n.base10_parse::<u64>()?,
+            _   => return Err(syn::Error::new(variant.span(), "variant requires a #[tag = N]")),
+        };
+        let flag = match attr(&variant.attrs, "flag")? {
+            Some(Expr::Lit(syn::ExprLit { lit: Lit::Bool(ref b), .. }))    => Some(b.value),
+            Some(expr)  => return Err(syn::Error::new(expr.span(), "expected a boolean")),
+            None        => None,
+        };
+        let legacy = match attr(&variant.attrs, "legacy_tag")? {
+            Some(Expr::Lit(syn::ExprLit { lit: Lit::Int(ref n), .. })) => {
+                Some(n.base10_parse::<u64>()?)
+            }
+            Some(expr)  => return Err(syn::Error::new(expr.span(), "expected an integer")),
+            None        => None,
+        };
+        let shape = shape(&variant.fields)?;
+        if legacy.is_some() && (shape.unit || flag.is_some()) {
+            return Err(syn::Error::new(variant.span(),
+                                       "only a variant with fields and no #[flag] may have a \
+                                        #[legacy_tag]"))
+        }
+        tagged.push((tag, flag, legacy, variant, shape));
+    }
+
+    let mut from_nums = Vec::new();
+    let mut encode = Vec::new();
+    let mut read = Vec::new();
+    for (i, &(tag, flag, legacy, variant, ref shape)) in tagged.iter().enumerate() {
+        let ident = &variant.ident;
+        let path = quote!(#ty::#ident);
+        let hex = Literal::byte_string(format!("{:x}", tag).as_bytes());
+        let pattern = pattern(path.clone(), shape);
+        let fields = encode_fields(shape, true);
+        let flag_field = if flag == Some(true) { Some(quote!(w.write_all(b".1")?;)) } else { None };
+        encode.push(quote!(#pattern => { w.write_all(#hex)?; #fields #flag_field Ok(()) }));
+
+        let shared: Vec<_> = tagged.iter().filter(|other| other.0 == tag).collect();
+        let repeated = tagged[..i].iter().any(|other| other.0 == tag);
+        if let Some(legacy) = legacy {
+            let owners: Vec<_> = tagged.iter().filter(|other| other.0 == legacy).collect();
+            if owners.len() != 1 || !owners[0].4.unit || owners[0].1.is_some()
+                || tagged.iter().filter(|other| other.2 == Some(legacy)).count() > 1 {
+                return Err(syn::Error::new(variant.span(),
+                    "a #[legacy_tag] must be the tag of one unit variant which has no #[flag]"))
+            }
+        }
+        let legacy = tagged.iter().find(|other| other.2 == Some(tag));
+        let tag = Literal::u64_unsuffixed(tag);
+        if let Some(&(_, _, _, data, ref data_shape)) = legacy {
+            // The unit variant, decoded as the variant which used to share its tag if any fields
+            // follow the tag.
+            let data = &data.ident;
+            let ctor = construct(quote!(#ty::#data), data_shape);
+            let fields_from_nums = from_nums_fields(data_shape);
+            let fields_read = read_fields(data_shape);
+            from_nums.push(quote! {
+                Some(#tag) => {
+                    let mut args = args.peekable();
+                    if args.peek().is_none() { return Some(#path) }
+                    #fields_from_nums
+                    Some(#ctor)
+                }
+            });
+            read.push(quote! {
+                #tag if fields.is_empty() => Ok(#path),
+                #tag => { #fields_read Ok(#ctor) }
+            });
+            continue
+        }
+        if shared.len() == 1 && flag.is_none() {
+            let ctor = construct(path, shape);
+            let fields_from_nums = from_nums_fields(shape);
+            let fields_read = read_fields(shape);
+            from_nums.push(quote!(Some(#tag) => { #fields_from_nums Some(#ctor) }));
+            read.push(quote!(#tag => { #fields_read Ok(#ctor) }));
+            continue
+        }
+
+        let (off, on) = match (shared.iter().find(|other| other.1 == Some(false)),
+                               shared.iter().find(|other| other.1 == Some(true))) {
+            (Some(off), Some(on)) if shared.len() == 2  => (off, on),
+            _   => return Err(syn::Error::new(variant.span(),
+                    "a tag may only be shared by a #[flag = false] and a #[flag = true] variant")),
+        };
+        if (on.4.unit, on.4.named, on.4.fields.len())
+            != (off.4.unit, off.4.named, off.4.fields.len()) {
+            return Err(syn::Error::new(on.3.span(),
+                                       "variants which share a tag must have the same fields"))
+        }
+        if repeated { continue }
+
+        // Both variants are decoded with the fields of the `false` variant.
+        let (off_ident, on_ident) = (&off.3.ident, &on.3.ident);
+        let off_ctor = construct(quote!(#ty::#off_ident), &off.4);
+        let on_ctor = construct(quote!(#ty::#on_ident), &off.4);
+        let fields_from_nums = from_nums_fields(&off.4);
+        let fields_read = read_fields(&off.4);
+        from_nums.push(quote! {
+            Some(#tag) => {
+                #fields_from_nums
+                let flag = <bool as ::notty_encoding::args::Argument>::from_nums(args.by_ref(),
+                                                                                 None);
+                Some(if flag.unwrap_or(false) { #on_ctor } else { #off_ctor })
+            }
+        });
+        read.push(quote! {
+            #tag => {
+                #fields_read
+                let flag = !fields.is_empty()
+                    && <bool as ::notty_encoding::args::Argument>::read_named(fields, "flag")?;
+                Ok(if flag { #on_ctor } else { #off_ctor })
+            }
+        });
+    }
+
+    Ok((quote! {
+        match args.next() {
+            #(#from_nums)*
+            _ => default,
+        }
+    }, quote! {
+        match *self {
+            #(#encode)*
+        }
+    }, quote! {
+        match fields.require()? {
+            #(#read)*
+            _ => Err(fields.error(::notty_encoding::ErrorKind::UnknownTag)),
+        }
+    }))
+}
+
+fn shape(fields: &Fields) -> syn::Result<Shape> {
+    let mut shape = Shape {
+        fields: Vec::new(),
+        named: matches!(*fields, Fields::Named(_)),
+        unit: matches!(*fields, Fields::Unit),
+    };
+    for (i, field) in fields.iter().enumerate() {
+        let name = match attr(&field.attrs, "name")? {
+            Some(Expr::Lit(syn::ExprLit { lit: Lit::Str(name), .. }))  => name,
+            Some(expr)  => return Err(syn::Error::new(expr.span(), "expected a string")),
+            None        => {
+                let name = field.ident.as_ref().map_or(String::from("value"), Ident::to_string);
+                LitStr::new(&name, Span::call_site())
+            }
+        };
+        let default = match attr(&field.attrs, "default")? {
+            Some(Expr::Lit(syn::ExprLit { lit: Lit::Str(expr), .. }))  => Some(expr.parse()?),
+            default                                                     => default,
+        };
+        let max = match attr(&field.attrs, "max")? {
+            Some(max @ Expr::Lit(syn::ExprLit { lit: Lit::Int(_), .. }))   => Some(max),
+            Some(expr)  => return Err(syn::Error::new(expr.span(), "expected an integer")),
+            None        => None,
+        };
+        let skip = field.attrs.iter().any(|attr| attr.path().is_ident("skip"));
+        if !skip && default.is_none()
+            && shape.fields.iter().any(|field: &Field| field.default.is_some()) {
+            return Err(syn::Error::new(field.span(),
+                                       "fields with defaults must follow fields without them"))
+        }
+        shape.fields.push(Field {
+            binding: field.ident.clone().unwrap_or_else(|| {
+                Ident::new(&format!("field{}", i), Span::call_site())
+            }),
+            ty: field.ty.clone(),
+            name: name,
+            default: default,
+            max: max,
+            skip: skip,
+        });
+    }
+    Ok(shape)
+}
+
+// Find the value of a `#[name = value]` attribute.
+fn attr(attrs: &[syn::Attribute], name: &str) -> syn::Result<Option<Expr>> {
+    for attr in attrs {
+        if attr.path().is_ident(name) {
+            return match attr.meta {
+                Meta::NameValue(ref meta)   => Ok(Some(meta.value.clone())),
+                _                           => {
+                    Err(syn::Error::new(attr.span(), format!("expected #[{} = ...]", name)))
+                }
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn bindings(shape: &Shape) -> Vec<&Ident> {
+    shape.fields.iter().map(|field| &field.binding).collect()
+}
+
+fn construct(path: Tokens, shape: &Shape) -> Tokens {
+    let bindings = bindings(shape);
+    if shape.unit {
+        path
+    } else if shape.named {
+        quote!(#path { #(#bindings: #bindings),* })
+    } else {
+        quote!(#path(#(#bindings),*))
+    }
+}
+
+// Skipped fields are matched without being bound.
+fn pattern(path: Tokens, shape: &Shape) -> Tokens {
+    let bindings = shape.fields.iter().map(|field| {
+        let binding = &field.binding;
+        match (field.skip, shape.named) {
+            (true, true)    => quote!(#binding: _),
+            (true, false)   => quote!(_),
+            (false, _)      => quote!(ref #binding),
+        }
+    });
+    if shape.unit {
+        path
+    } else if shape.named {
+        quote!(#path { #(#bindings),* })
+    } else {
+        quote!(#path(#(#bindings),*))
+    }
+}
+
+// Decode each field leniently, returning the default if a field without a default of its own
+// cannot be decoded.
+fn from_nums_fields(shape: &Shape) -> Tokens {
+    let fields = shape.fields.iter().map(|field| {
+        let Field { ref binding, ref ty, ref default, ref max, skip, .. } = *field;
+        if skip { return quote!(let #binding = ::std::default::Default::default();) }
+        let value = quote! {
+            <#ty as ::notty_encoding::args::Argument>::from_nums(args.by_ref(), None)
+        };
+        let value = match *max {
+            Some(ref max)   => quote!(#value.filter(|value| *value <= #max)),
+            None            => value,
+        };
+        match *default {
+            Some(ref default)   => quote!(let #binding = #value.unwrap_or(#default);),
+            None                => quote! {
+                let #binding = match #value {
+                    Some(value) => value,
+                    None        => return default,
+                };
+            },
+        }
+    });
+    quote!(#(#fields)*)
+}
+
+fn read_fields(shape: &Shape) -> Tokens {
+    let fields = shape.fields.iter().map(|field| {
+        let Field { ref binding, ref ty, ref name, ref default, ref max, skip } = *field;
+        if skip { return quote!(let #binding = ::std::default::Default::default();) }
+        let read = quote! {
+            <#ty as ::notty_encoding::args::Argument>::read_named(fields, #name)?
+        };
+        let read = match *default {
+            Some(ref default)   => quote!(if fields.is_empty() { #default } else { #read }),
+            None                => read,
+        };
+        let check = max.as_ref().map(|max| quote! {
+            if #binding > #max {
+                return Err(fields.error(::notty_encoding::ErrorKind::OutOfRange))
+            }
+        });
+        quote!(let #binding = #read; #check)
+    });
+    quote!(#(#fields)*)
+}
+
+fn encode_fields(shape: &Shape, tagged: bool) -> Tokens {
+    let fields = shape.fields.iter().filter(|field| !field.skip).enumerate().map(|(i, field)| {
+        let Field { ref binding, ref ty, .. } = *field;
+        let separator = if tagged || i > 0 { Some(quote!(w.write_all(b".")?;)) } else { None };
+        quote! {
+            #separator
+            <#ty as ::notty_encoding::args::Argument>::encode_to(#binding, w)?;
+        }
+    });
+    quote!(#(#fields)*)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use syn::DeriveInput;
+
+    use super::derive;
+
+    fn error(input: &str) -> String {
+        let input: DeriveInput = syn::parse_str(input).unwrap();
+        derive(&input).expect_err("the derive should fail").to_string()
+    }
+
+    #[test]
+    fn deriving() {
+        let input: DeriveInput = syn::parse_str("
+            enum Arg {
+                #[tag = 1] A(#[max = 3] u8, #[default = \"Dir::Up\"] Dir),
+                #[tag = 2] #[flag = false] Off,
+                #[tag = 2] #[flag = true] On,
+                #[tag = 3] Unit,
+                #[tag = 4] #[legacy_tag = 3] Data(u32, #[skip] ()),
+            }
+        ").unwrap();
+        assert!(derive(&input).is_ok());
+    }
+
+    #[test]
+    fn shape_errors() {
+        assert_eq!(error("union U { a: u8 }"), "unions cannot derive Argument");
+        assert_eq!(error("enum E { A }"), "variant requires a #[tag = N]");
+        assert_eq!(error("enum E { #[tag = \"1\"] A }"), "variant requires a #[tag = N]");
+        assert_eq!(error("struct S(#[default = 1] u8, u8);"),
+                   "fields with defaults must follow fields without them");
+        assert_eq!(error("#[new = Self::new] struct S(u8);"), "expected a string");
+    }
+
+    #[test]
+    fn attribute_errors() {
+        assert_eq!(error("struct S(#[max = \"9\"] u8);"), "expected an integer");
+        assert_eq!(error("struct S(#[max = u8::MAX] u8);"), "expected an integer");
+        assert_eq!(error("struct S(#[default = \"1 +\"] u8);"),
+                   "unexpected end of input, expected an expression");
+        assert_eq!(error("struct S(#[name = value] u8);"), "expected a string");
+        assert_eq!(error("struct S(#[name] u8);"), "expected #[name = ...]");
+        assert_eq!(error("enum E { #[tag = 1] #[flag = 1] A }"), "expected a boolean");
+    }
+
+    #[test]
+    fn shared_tag_errors() {
+        let shared = "a tag may only be shared by a #[flag = false] and a #[flag = true] variant";
+        assert_eq!(error("enum E { #[tag = 1] A, #[tag = 1] B(u8) }"), shared);
+        assert_eq!(error("enum E { #[tag = 1] #[flag = true] A }"), shared);
+        assert_eq!(error("enum E { #[tag = 1] #[flag = true] A, #[tag = 1] #[flag = true] B }"),
+                   shared);
+        let input = "enum E { #[tag = 1] #[flag = false] A, #[tag = 1] #[flag = true] B(u8) }";
+        assert_eq!(error(input), "variants which share a tag must have the same fields");
+    }
+
+    #[test]
+    fn legacy_tag_errors() {
+        let owner = "a #[legacy_tag] must be the tag of one unit variant which has no #[flag]";
+        assert_eq!(error("enum E { #[tag = 1] A(u8), #[tag = 2] #[legacy_tag = 1] B(u8) }"),
+                   owner);
+        assert_eq!(error("enum E { #[tag = 2] #[legacy_tag = 1] B(u8) }"), owner);
+        assert_eq!(error("enum E { #[tag = 1] A, #[tag = 2] #[legacy_tag = 1] B }"),
+                   "only a variant with fields and no #[flag] may have a #[legacy_tag]");
+        assert_eq!(error("enum E { #[tag = 1] A, #[tag = 2] #[legacy_tag = \"1\"] B(u8) }"),
+                   "expected an integer");
+    }
+}
@@ -0,0 +1,487 @@
+use std::mem;
+
+use args::{Area, Color, Coords, Direction, Movement, Style};
+use cmds::*;
+use parser::{Event, Parser};
+
+use self::State::*;
+
+const ESC: char = '\x1b';
+const BEL: char = '\x07';
+
+// The longest escape sequence which is buffered; longer sequences are passed through as text.
+const MAX_LEN: usize = 4096;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum State {
+    /// Processing text.
+    Ground,
+    /// Seen `ESC`.
+    Escape,
+    /// Inside a control sequence, introduced by `ESC [`.
+    Csi,
+    /// Inside an operating system command, introduced by `ESC ]`.
+    Osc,
+    /// Seen `ESC` inside an operating system command; this should be the first byte of `ST`.
+    OscEscape,
+}
+
+/// A resumable parser for output which mixes text with ANSI escape sequences, converting the
+/// sequences which have a notty equivalent into commands.
+///
+/// Cursor movement (`CUP`, `CUU`, `CUD`, `CUF`, `CUB`, `CHA`, `VPA`, `IND`, `RI` and `NEL`),
+/// erasure (`ED`, `EL` and `ECH`), insertion and deletion (`ICH`, `DCH`, `IL` and `DL`), scrolling
+/// (`SU` and `SD`), `SGR` text styles and `OSC` window titles are converted. Every other sequence
+/// is passed through unchanged as text, as are the parameters of an `SGR` sequence which have no
+/// notty equivalent.
+///
+/// Notty escape sequences in the input are recognized as well, so that output which mixes both
+/// protocols produces a single stream of events.
+#[derive(Clone, Debug)]
+pub struct Importer {
+    parser: Parser,
+    ansi: Ansi,
+}
+
+#[derive(Clone, Debug)]
+struct Ansi {
+    state: State,
+    seq: String,
+}
+
+impl Importer {
+    pub fn new() -> Importer {
+        Importer::with_parser(Parser::new())
+    }
+
+    /// Create an importer which recognizes notty sequences with the given parser.
+    pub fn with_parser(parser: Parser) -> Importer {
+        Importer {
+            parser: parser,
+            ansi: Ansi { state: Ground, seq: String::new() },
+        }
+    }
+
+    /// Returns true if the importer is holding onto an incomplete escape sequence or character.
+    pub fn is_pending(&self) -> bool {
+        self.ansi.state != Ground || self.parser.is_pending()
+    }
+
+    /// Parse a chunk of input, passing each event recognized in it to the callback.
+    pub fn feed<F>(&mut self, input: &[u8], mut f: F) where F: FnMut(Event) {
+        let Importer { ref mut parser, ref mut ansi } = *self;
+        parser.feed(input, |event| ansi.event(event, &mut f));
+    }
+
+    /// Signal the end of the input, passing anything the importer was still holding onto to the
+    /// callback.
+    pub fn finish<F>(&mut self, mut f: F) where F: FnMut(Event) {
+        let Importer { ref mut parser, ref mut ansi } = *self;
+        parser.finish(|event| ansi.event(event, &mut f));
+        ansi.flush(&mut f);
+    }
+}
+
+impl Default for Importer {
+    fn default() -> Importer {
+        Importer::new()
+    }
+}
+
+impl Ansi {
+    fn event<F>(&mut self, event: Event, f: &mut F) where F: FnMut(Event) {
+        match event {
+            Event::Text(text)   => self.text(text, f),
+            event               => {
+                // A notty sequence interrupts any ANSI sequence in progress.
+                self.flush(f);
+                f(event)
+            }
+        }
+    }
+
+    fn text<F>(&mut self, text: &str, f: &mut F) where F: FnMut(Event) {
+        let mut text_start = 0;
+        let mut chars = text.char_indices().peekable();
+        while let Some(&(i, c)) = chars.peek() {
+            let in_sequence = self.state != Ground || c == ESC;
+            match self.state {
+                Ground      => if c == ESC {
+                    if text_start < i { f(Event::Text(&text[text_start..i])); }
+                    self.seq.push(c);
+                    self.state = Escape;
+                },
+                Escape      => match c {
+                    '['             => self.push(c, Csi, f),
+                    ']'             => self.push(c, Osc, f),
+                    'D' | 'M' | 'E' => {
+                        let movement = match c {
+                            'D' => Movement::IndexTo(Direction::Down, 1),
+                            'M' => Movement::IndexTo(Direction::Up, 1),
+                            _   => Movement::NextLine(1),
+                        };
+                        f(Event::Command(Move::new(movement).into()));
+                        self.seq.clear();
+                        self.state = Ground;
+                    }
+                    _               => {
+                        // Not a sequence which is converted; the current character is
+                        // reconsidered as text.
+                        self.flush(f);
+                        text_start = i;
+                        continue
+                    }
+                },
+                Csi         => match c {
+                    '\x20'..='\x3f' => self.push(c, Csi, f),
+                    '\x40'..='\x7e' => {
+                        self.seq.push(c);
+                        self.csi(f);
+                        self.seq.clear();
+                        self.state = Ground;
+                    }
+                    _               => {
+                        self.flush(f);
+                        text_start = i;
+                        continue
+                    }
+                },
+                Osc         => match c {
+                    BEL     => {
+                        self.osc("\x07", f);
+                        self.state = Ground;
+                    }
+                    ESC     => self.state = OscEscape,
+                    _       => self.push(c, Osc, f),
+                },
+                OscEscape   => match c {
+                    '\\'    => {
+                        self.osc("\x1b\\", f);
+                        self.state = Ground;
+                    }
+                    _       => {
+                        // The command was interrupted by another escape sequence.
+                        self.state = Osc;
+                        self.flush(f);
+                        self.seq.push(ESC);
+                        self.state = Escape;
+                        continue
+                    }
+                },
+            }
+            chars.next();
+            if in_sequence {
+                text_start = i + c.len_utf8();
+            }
+        }
+        if self.state == Ground && text_start < text.len() {
+            f(Event::Text(&text[text_start..]));
+        }
+    }
+
+    // Buffer a character of a sequence, passing the sequence through as text if it is too long.
+    fn push<F>(&mut self, c: char, state: State, f: &mut F) where F: FnMut(Event) {
+        self.seq.push(c);
+        self.state = state;
+        if self.seq.len() > MAX_LEN {
+            self.flush(f);
+        }
+    }
+
+    // Pass an incomplete sequence through as text.
+    fn flush<F>(&mut self, f: &mut F) where F: FnMut(Event) {
+        if self.state == OscEscape {
+            self.seq.push(ESC);
+        }
+        if !self.seq.is_empty() {
+            f(Event::Text(&self.seq));
+            self.seq.clear();
+        }
+        self.state = Ground;
+    }
+
+    fn csi<F>(&mut self, f: &mut F) where F: FnMut(Event) {
+        let (params, final_byte) = self.seq[2..].split_at(self.seq.len() - 3);
+        // Private parameters, intermediate bytes and subparameters are not understood.
+        if !params.bytes().all(|b| b.is_ascii_digit() || b == b';') {
+            return f(Event::Text(&self.seq))
+        }
+        let params: Vec<u32> = params.split(';').map(|param| {
+            if param.is_empty() { 0 } else { param.parse().unwrap_or(u32::MAX) }
+        }).collect();
+        let n = params[0];
+        let count = n.max(1);
+        let cmd: Command = match final_byte {
+            "H" | "f"   => {
+                let y = count - 1;
+                let x = params.get(1).map_or(0, |&x| x.max(1) - 1);
+                Move::new(Movement::Position(Coords { x: x, y: y })).into()
+            }
+            "A"         => Move::new(Movement::To(Direction::Up, count, false)).into(),
+            "B"         => Move::new(Movement::To(Direction::Down, count, false)).into(),
+            "C"         => Move::new(Movement::To(Direction::Right, count, false)).into(),
+            "D"         => Move::new(Movement::To(Direction::Left, count, false)).into(),
+            "G"         => Move::new(Movement::Column(count - 1)).into(),
+            "d"         => Move::new(Movement::Row(count - 1)).into(),
+            // ED 0 erases from the cursor, rather than from the start of the cursor row.
+            "J"         => match n {
+                0   => Erase::new(Area::CursorTo(Movement::ToEnd)).into(),
+                1   => Erase::new(Area::CursorTo(Movement::ToBeginning)).into(),
+                2   => Erase::new(Area::WholeScreen).into(),
+                _   => return f(Event::Text(&self.seq)),
+            },
+            "K"         => match n {
+                0   => Erase::new(Area::CursorTo(Movement::ToEdge(Direction::Right))).into(),
+                1   => Erase::new(Area::CursorTo(Movement::ToEdge(Direction::Left))).into(),
+                2   => Erase::new(Area::CursorRow).into(),
+                _   => return f(Event::Text(&self.seq)),
+            },
+            "X"         => match count {
+                1   => Erase::new(Area::CursorCell).into(),
+                n   => Erase::new(Area::CursorTo(Movement::To(Direction::Right, n - 1, false)))
+                           .into(),
+            },
+            "@"         => InsertBlank::new(count).into(),
+            "P"         => RemoveChars::new(count).into(),
+            "L"         => InsertRows::new(count, true).into(),
+            "M"         => RemoveRows::new(count, true).into(),
+            "S"         => ScrollScreen::new(Direction::Up, count).into(),
+            "T"         => ScrollScreen::new(Direction::Down, count).into(),
+            "m"         => return sgr(&params, f),
+            _           => return f(Event::Text(&self.seq)),
+        };
+        f(Event::Command(cmd))
+    }
+
+    // Convert an operating system command, given the terminator it ended with.
+    fn osc<F>(&mut self, terminator: &str, f: &mut F) where F: FnMut(Event) {
+        match self.seq[2..].split_once(';') {
+            Some(("0", title)) | Some(("2", title)) => {
+                f(Event::Command(SetTitle(String::from(title)).into()))
+            }
+            // Only numeric ids can be kept; links with other ids are imported without them.
+            Some(("8", link))                       => match link.split_once(';') {
+                Some((_, ""))       => f(Event::Command(EndHyperlink.into())),
+                Some((params, url)) => {
+                    let id = params.split(':').filter_map(|param| param.strip_prefix("id="))
+                                   .filter_map(|id| id.parse().ok()).next();
+                    f(Event::Command(BeginHyperlink::new(id, String::from(url)).into()))
+                }
+                None                => {
+                    f(Event::Text(&self.seq));
+                    f(Event::Text(terminator));
+                }
+            },
+            _                                       => {
+                f(Event::Text(&self.seq));
+                f(Event::Text(terminator));
+            }
+        }
+        self.seq.clear();
+    }
+}
+
+// Convert the parameters of an SGR sequence into styles, which are set together. The parameters
+// which cannot be converted are passed through in a sequence of their own, after the styles set
+// before them.
+fn sgr<F>(params: &[u32], f: &mut F) where F: FnMut(Event) {
+    let mut styles = vec![];
+    let mut rest = String::new();
+    let pass = |styles: &mut Vec<Style>, rest: &mut String, f: &mut F| {
+        if !styles.is_empty() {
+            f(Event::Command(SetTextStyle(mem::take(styles)).into()));
+        }
+        if !rest.is_empty() {
+            f(Event::Text(&format!("\x1b[{}m", rest)));
+            rest.clear();
+        }
+    };
+    let mut i = 0;
+    while i < params.len() {
+        let (style, len) = match params[i] {
+            0               => {
+                pass(&mut styles, &mut rest, f);
+                f(Event::Command(DefaultTextStyle.into()));
+                i += 1;
+                continue
+            }
+            1               => (Some(Style::Bold(true)), 1),
+            3               => (Some(Style::Italic(true)), 1),
+            4               => (Some(Style::Underline(1)), 1),
+            5 | 6           => (Some(Style::Blink(true)), 1),
+            7               => (Some(Style::InvertColors(true)), 1),
+            9               => (Some(Style::Strikethrough(true)), 1),
+            21              => (Some(Style::Underline(2)), 1),
+            22              => (Some(Style::Bold(false)), 1),
+            23              => (Some(Style::Italic(false)), 1),
+            24              => (Some(Style::Underline(0)), 1),
+            25              => (Some(Style::Blink(false)), 1),
+            27              => (Some(Style::InvertColors(false)), 1),
+            29              => (Some(Style::Strikethrough(false)), 1),
+            n @ 30..=37     => (Some(Style::FgColor(Color::Palette(n as u8 - 30))), 1),
+            39              => (Some(Style::FgColor(Color::Default)), 1),
+            n @ 40..=47     => (Some(Style::BgColor(Color::Palette(n as u8 - 40))), 1),
+            49              => (Some(Style::BgColor(Color::Default)), 1),
+            n @ 90..=97     => (Some(Style::FgColor(Color::Palette(n as u8 - 82))), 1),
+            n @ 100..=107   => (Some(Style::BgColor(Color::Palette(n as u8 - 92))), 1),
+            59              => (Some(Style::UnderlineColor(Color::Default)), 1),
+            n @ 38 | n @ 48 | n @ 58 => match extended_color(&params[i + 1..]) {
+                Some((color, len)) if n == 38   => (Some(Style::FgColor(color)), len + 1),
+                Some((color, len)) if n == 48   => (Some(Style::BgColor(color)), len + 1),
+                Some((color, len))              => (Some(Style::UnderlineColor(color)), len + 1),
+                // The length of a malformed color is unknown, so the rest are passed through.
+                None                            => (None, params.len() - i),
+            },
+            _               => (None, 1),
+        };
+        match style {
+            Some(style) => styles.push(style),
+            None        => for param in &params[i..i + len] {
+                if !rest.is_empty() { rest.push(';'); }
+                rest.push_str(&param.to_string());
+            },
+        }
+        i += len;
+    }
+    pass(&mut styles, &mut rest, f);
+}
+
+// Read the color of an extended color parameter, returning the number of parameters it used.
+fn extended_color(params: &[u32]) -> Option<(Color, usize)> {
+    let byte = |i: usize| params.get(i).and_then(|&n| if n <= 0xff { Some(n as u8) } else { None });
+    match params.first() {
+        Some(&5)    => byte(1).map(|n| (Color::Palette(n), 2)),
+        Some(&2)    => match (byte(1), byte(2), byte(3)) {
+            (Some(r), Some(g), Some(b)) => Some((Color::True(r, g, b), 4)),
+            _                           => None,
+        },
+        _           => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use args::*;
+    use cmds::*;
+    use parser::Event;
+
+    use super::*;
+
+    #[derive(Debug, Eq, PartialEq)]
+    enum Owned {
+        Text(String),
+        Command(Command),
+    }
+
+    // Feed the input in chunks of the given size, joining adjacent runs of text.
+    fn import(input: &str, chunk: usize) -> Vec<Owned> {
+        let mut events = Vec::new();
+        {
+            let mut push = |event: Event| match event {
+                Event::Text(s)      => match events.last_mut() {
+                    Some(&mut Owned::Text(ref mut text))    => text.push_str(s),
+                    _                                       => {
+                        events.push(Owned::Text(String::from(s)))
+                    }
+                },
+                Event::Command(cmd) => events.push(Owned::Command(cmd)),
+                Event::Malformed(b) => panic!("malformed: {:?}", b),
+            };
+            let mut importer = Importer::new();
+            for chunk in input.as_bytes().chunks(chunk) {
+                importer.feed(chunk, &mut push);
+            }
+            importer.finish(&mut push);
+        }
+        events
+    }
+
+    fn text(s: &str) -> Owned {
+        Owned::Text(String::from(s))
+    }
+
+    fn cmd<C: Into<Command>>(cmd: C) -> Owned {
+        Owned::Command(cmd.into())
+    }
+
+    #[test]
+    fn import_in_any_chunk_size() {
+        let input = format!("a\x1b[2;5Hb\x1b[1;31mcö\x1b[Kd\x1b]2;tïtle\x07\x1b[3P{}\x1b[?25l",
+                            SetTitle(String::from("notty")).encode());
+        let expected = vec![
+            text("a"),
+            cmd(Move::new(Movement::Position(Coords { x: 4, y: 1 }))),
+            text("b"),
+            cmd(SetTextStyle(vec![Style::Bold(true), Style::FgColor(Color::Palette(1))])),
+            text("cö"),
+            cmd(Erase::new(Area::CursorTo(Movement::ToEdge(Direction::Right)))),
+            text("d"),
+            cmd(SetTitle(String::from("tïtle"))),
+            cmd(RemoveChars::new(3)),
+            cmd(SetTitle(String::from("notty"))),
+            text("\x1b[?25l"),
+        ];
+        for chunk in 1..input.len() + 1 {
+            assert_eq!(import(&input, chunk), expected, "chunk size {}", chunk);
+        }
+    }
+
+    #[test]
+    fn import_sgr() {
+        assert_eq!(import("\x1b[m\x1b[2;4;38;5;200;48;2;1;2;3;8m", 1), vec![
+            cmd(DefaultTextStyle),
+            cmd(SetTextStyle(vec![Style::Underline(1), Style::FgColor(Color::Palette(200)),
+                                  Style::BgColor(Color::True(1, 2, 3))])),
+            text("\x1b[2;8m"),
+        ]);
+        assert_eq!(import("\x1b[2;0;97;38;5m", 3), vec![
+            text("\x1b[2m"),
+            cmd(DefaultTextStyle),
+            cmd(SetTextStyle(vec![Style::FgColor(Color::Palette(15))])),
+            text("\x1b[38;5m"),
+        ]);
+        assert_eq!(import("\x1b[4;58;2;255;0;0m\x1b[59m", 2), vec![
+            cmd(SetTextStyle(vec![Style::Underline(1),
+                                  Style::UnderlineColor(Color::True(255, 0, 0))])),
+            cmd(SetTextStyle(vec![Style::UnderlineColor(Color::Default)])),
+        ]);
+    }
+
+    #[test]
+    fn import_erase_and_edit() {
+        assert_eq!(import("\x1b[J\x1b[2J\x1b[X\x1b[4X\x1b[2@\x1b[L\x1bD\x1b[3S", 2), vec![
+            cmd(Erase::new(Area::CursorTo(Movement::ToEnd))),
+            cmd(Erase::new(Area::WholeScreen)),
+            cmd(Erase::new(Area::CursorCell)),
+            cmd(Erase::new(Area::CursorTo(Movement::To(Direction::Right, 3, false)))),
+            cmd(InsertBlank::new(2)),
+            cmd(InsertRows::new(1, true)),
+            cmd(Move::new(Movement::IndexTo(Direction::Down, 1))),
+            cmd(ScrollScreen::new(Direction::Up, 3)),
+        ]);
+    }
+
+    #[test]
+    fn import_hyperlinks() {
+        let input = "\x1b]8;id=12;http://a\x1b\\x\x1b]8;;\x07\x1b]8;foo=1:id=b;file:///c\x1b\\";
+        assert_eq!(import(input, 3), vec![
+            cmd(BeginHyperlink::new(Some(12), String::from("http://a"))),
+            text("x"),
+            cmd(EndHyperlink),
+            cmd(BeginHyperlink::new(None, String::from("file:///c"))),
+        ]);
+        assert_eq!(import("\x1b]8;http://a\x07", 1), vec![text("\x1b]8;http://a\x07")]);
+    }
+
+    #[test]
+    fn pass_through_other_sequences() {
+        assert_eq!(import("\x1b(B\x1b[5n\x1b]7;file://a\x1b\\x\x1b]2;t\x1b[A\x1b", 1), vec![
+            text("\x1b(B\x1b[5n\x1b]7;file://a\x1b\\x\x1b]2;t"),
+            cmd(Move::new(Movement::To(Direction::Up, 1, false))),
+            text("\x1b"),
+        ]);
+        assert_eq!(import("\x1b[1\n2m", 1), vec![text("\x1b[1\n2m")]);
+    }
+
+}
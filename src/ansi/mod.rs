@@ -0,0 +1,8 @@
+//! Conversion between notty commands and ANSI escape sequences, for terminals and programs which
+//! do not understand notty's own sequences.
+
+mod import;
+mod translate;
+
+pub use self::import::Importer;
+pub use self::translate::{Translation, translate};
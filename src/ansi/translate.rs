@@ -0,0 +1,354 @@
+use std::cmp;
+use std::io;
+
+use args::{Area, Color, Direction, Movement, Region, Style};
+use args::Direction::*;
+use cmds::*;
+
+use self::Translation::*;
+
+// A count large enough to reach the edge of any screen; cursor movements stop at the edge.
+const EDGE: u32 = 9999;
+
+/// How closely a command was reproduced by `translate`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Translation {
+    /// The command was written as ANSI sequences with the same effect.
+    Exact,
+    /// The command was written as the closest ANSI sequences, whose effect differs in some cases.
+    Approximate,
+    /// The command cannot be expressed in ANSI, and nothing was written.
+    Unsupported,
+}
+
+/// Write the ANSI escape sequences which most closely reproduce a command.
+///
+/// Panels, media, tooltips, input modes, cursor styles, and styles and hyperlinks applied to an
+/// area have no ANSI equivalent; for these nothing is written and `Unsupported` is returned.
+pub fn translate(cmd: &Command, w: &mut dyn io::Write) -> io::Result<Translation> {
+    match *cmd {
+        Command::Move(Move { movement })            => movement_to(movement, w),
+        Command::ScrollScreen(ref cmd)              => scroll(cmd.dir, cmd.n, w),
+        Command::Erase(Erase { area })              => erase(area, w),
+        Command::RemoveChars(RemoveChars { count }) => repeat_csi(count, 'P', w),
+        Command::InsertBlank(InsertBlank { count }) => repeat_csi(count, '@', w),
+        Command::RemoveRows(ref cmd)                => rows(cmd.count, cmd.include, 'M', w),
+        Command::InsertRows(ref cmd)                => rows(cmd.count, cmd.include, 'L', w),
+        Command::SetTextStyle(ref cmd)              => sgr(&cmd.0, w),
+        Command::DefaultTextStyle(_)                => { w.write_all(b"\x1b[0m")?; Ok(Exact) }
+        Command::SetTitle(SetTitle(ref title))      => {
+            w.write_all(b"\x1b]2;")?;
+            osc_text(title, w)
+        }
+        Command::BeginHyperlink(ref link)           => {
+            match link.id {
+                Some(id)    => write!(w, "\x1b]8;id={};", id)?,
+                None        => w.write_all(b"\x1b]8;;")?,
+            }
+            osc_text(&link.url, w)
+        }
+        Command::EndHyperlink(_)                    => {
+            w.write_all(b"\x1b]8;;\x1b\\")?;
+            Ok(Exact)
+        }
+        _                                           => Ok(Unsupported),
+    }
+}
+
+// Write a CSI sequence which takes a count, writing nothing for a count of 0, which most
+// sequences treat as 1.
+fn repeat_csi(n: u32, final_byte: char, w: &mut dyn io::Write) -> io::Result<Translation> {
+    if n > 0 { write!(w, "\x1b[{}{}", n, final_byte)?; }
+    Ok(Exact)
+}
+
+fn movement_to(movement: Movement, w: &mut dyn io::Write) -> io::Result<Translation> {
+    match movement {
+        Movement::Position(coords)  => {
+            write!(w, "\x1b[{};{}H", coords.y as u64 + 1, coords.x as u64 + 1)?;
+            Ok(Exact)
+        }
+        Movement::To(dir, n, wrap)  => {
+            repeat_csi(n, cursor_final(dir), w)?;
+            Ok(if wrap { Approximate } else { Exact })
+        }
+        Movement::ToEdge(Up)        => { w.write_all(b"\x1b[d")?; Ok(Exact) }
+        Movement::ToEdge(Left)      => { w.write_all(b"\x1b[G")?; Ok(Exact) }
+        Movement::ToEdge(dir)       => repeat_csi(EDGE, cursor_final(dir), w),
+        // IND and RI move the cursor a row, scrolling if it is at the edge of the screen.
+        Movement::IndexTo(Up, n)    => { index(n, b"\x1bM", w)?; Ok(Exact) }
+        Movement::IndexTo(Down, n)  => { index(n, b"\x1bD", w)?; Ok(Exact) }
+        Movement::IndexTo(dir, n)   => {
+            repeat_csi(n, cursor_final(dir), w)?;
+            Ok(Approximate)
+        }
+        // Vertical tabs move by cells, so they are sent as CUU and CUD rather than CBT and CHT.
+        Movement::Tab(Up, n, _)     => repeat_csi(n, cursor_final(Up), w),
+        Movement::Tab(Down, n, _)   => repeat_csi(n, cursor_final(Down), w),
+        Movement::Tab(dir, n, wrap) => {
+            repeat_csi(n, if dir == Left { 'Z' } else { 'I' }, w)?;
+            Ok(if wrap { Approximate } else { Exact })
+        }
+        Movement::PreviousLine(n)   => { w.write_all(b"\r")?; index(n, b"\x1bM", w)?; Ok(Exact) }
+        Movement::NextLine(n)       => { w.write_all(b"\r")?; index(n, b"\x1bD", w)?; Ok(Exact) }
+        Movement::Column(n)         => { write!(w, "\x1b[{}G", n as u64 + 1)?; Ok(Exact) }
+        Movement::Row(n)            => { write!(w, "\x1b[{}d", n as u64 + 1)?; Ok(Exact) }
+        Movement::ToBeginning       => { w.write_all(b"\x1b[H")?; Ok(Exact) }
+        Movement::ToEnd             => { write!(w, "\x1b[{};{}H", EDGE, EDGE)?; Ok(Exact) }
+    }
+}
+
+// The final byte of the CUU, CUD, CUB and CUF sequences.
+fn cursor_final(dir: Direction) -> char {
+    match dir {
+        Up      => 'A',
+        Down    => 'B',
+        Left    => 'D',
+        Right   => 'C',
+    }
+}
+
+// No screen is taller than EDGE rows, so indexing further only scrolls blank lines.
+fn index(n: u32, control: &[u8], w: &mut dyn io::Write) -> io::Result<()> {
+    for _ in 0..cmp::min(n, EDGE) {
+        w.write_all(control)?;
+    }
+    Ok(())
+}
+
+fn scroll(dir: Direction, n: u32, w: &mut dyn io::Write) -> io::Result<Translation> {
+    match dir {
+        Up      => repeat_csi(n, 'S', w),
+        Down    => repeat_csi(n, 'T', w),
+        _       => Ok(Unsupported),
+    }
+}
+
+fn erase(area: Area, w: &mut dyn io::Write) -> io::Result<Translation> {
+    match area {
+        Area::CursorCell                                => w.write_all(b"\x1b[X")?,
+        Area::CursorRow                                 => w.write_all(b"\x1b[2K")?,
+        Area::WholeScreen                               => w.write_all(b"\x1b[2J")?,
+        Area::BelowCursor(true)                         => w.write_all(b"\x1b[2K\x1b[J")?,
+        // This erases the cursor row as well if the cursor is in the bottom row.
+        Area::BelowCursor(false)                        => {
+            w.write_all(b"\x1b7\x1b[E\x1b[J\x1b8")?;
+            return Ok(Approximate)
+        }
+        Area::CursorTo(Movement::ToEdge(Right))         => w.write_all(b"\x1b[K")?,
+        Area::CursorTo(Movement::ToEdge(Left))          => w.write_all(b"\x1b[1K")?,
+        Area::CursorTo(Movement::ToEnd)                 => w.write_all(b"\x1b[J")?,
+        Area::CursorTo(Movement::ToBeginning)           => w.write_all(b"\x1b[1J")?,
+        Area::CursorTo(Movement::To(Right, n, false))   => {
+            write!(w, "\x1b[{}X", n as u64 + 1)?
+        }
+        // An empty or inverted range of rows erases nothing.
+        Area::Rows(top, bottom)                         => {
+            let bottom = cmp::min(bottom, EDGE);
+            if top < bottom { erase_region(Region::new(0, top, EDGE, bottom), w)? }
+        }
+        Area::Bound(region)                             => erase_region(region, w)?,
+        _                                               => return Ok(Unsupported),
+    }
+    Ok(Exact)
+}
+
+// Erase each row of a region with ECH, restoring the cursor afterward. Rows past the edge of the
+// largest screen are left out.
+fn erase_region(region: Region, w: &mut dyn io::Write) -> io::Result<()> {
+    if region.is_empty() { return Ok(()) }
+    w.write_all(b"\x1b7")?;
+    for y in region.top..cmp::min(region.bottom, EDGE) {
+        write!(w, "\x1b[{};{}H\x1b[{}X", y as u64 + 1, region.left as u64 + 1, region.width())?;
+    }
+    w.write_all(b"\x1b8")
+}
+
+// IL and DL act at the cursor row; to act below it, the cursor is moved down a row first, which
+// fails if it is in the bottom row.
+fn rows(n: u32, include: bool, final_byte: char, w: &mut dyn io::Write)
+        -> io::Result<Translation> {
+    if include || n == 0 {
+        repeat_csi(n, final_byte, w)
+    } else {
+        write!(w, "\x1b7\x1b[B\x1b[{}{}\x1b8", n, final_byte)?;
+        Ok(Approximate)
+    }
+}
+
+// Styles are written together in a single SGR sequence. Styles which ANSI cannot express are
+// left out, making the translation approximate, or unsupported if none of the styles are left.
+fn sgr(styles: &[Style], w: &mut dyn io::Write) -> io::Result<Translation> {
+    let params: Vec<String> = styles.iter().filter_map(|&style| sgr_params(style)).collect();
+    if params.is_empty() && !styles.is_empty() { return Ok(Unsupported) }
+    if !params.is_empty() {
+        write!(w, "\x1b[{}m", params.join(";"))?;
+    }
+    Ok(if params.len() == styles.len() { Exact } else { Approximate })
+}
+
+fn sgr_params(style: Style) -> Option<String> {
+    Some(match style {
+        Style::Underline(0)         => String::from("24"),
+        Style::Underline(1)         => String::from("4"),
+        Style::Underline(_)         => String::from("21"),
+        Style::Bold(on)             => toggle(on, 1, 22),
+        Style::Italic(on)           => toggle(on, 3, 23),
+        Style::Blink(on)            => toggle(on, 5, 25),
+        Style::InvertColors(on)     => toggle(on, 7, 27),
+        Style::Strikethrough(on)    => toggle(on, 9, 29),
+        Style::FgColor(color)       => color_sgr(color, 30, 90),
+        Style::BgColor(color)       => color_sgr(color, 40, 100),
+        // Underline colors have no basic forms, so palette colors always use the extended form.
+        Style::UnderlineColor(color)    => match color {
+            Color::Default          => String::from("59"),
+            Color::Palette(n)       => format!("58;5;{}", n),
+            Color::True(r, g, b)    => format!("58;2;{};{};{}", r, g, b),
+        },
+        // Underline shapes are set with sub-parameters of 4, which also turn on the underline.
+        Style::Opacity(_) | Style::Configured(_) | Style::UnderlineShape(_) => return None,
+    })
+}
+
+fn toggle(on: bool, set: u8, reset: u8) -> String {
+    (if on { set } else { reset }).to_string()
+}
+
+// Colors are written in the shortest form which terminals understand: the 16 basic colors have
+// their own parameters, and the rest of the palette and true colors use the extended forms.
+fn color_sgr(color: Color, base: u8, bright: u8) -> String {
+    match color {
+        Color::Default              => (base + 9).to_string(),
+        Color::Palette(n @ 0..=7)   => (base + n).to_string(),
+        Color::Palette(n @ 8..=15)  => (bright + n - 8).to_string(),
+        Color::Palette(n)           => format!("{};5;{}", base + 8, n),
+        Color::True(r, g, b)        => format!("{};2;{};{};{}", base + 8, r, g, b),
+    }
+}
+
+// Write the text at the end of an OSC sequence and terminate it. Control characters are removed
+// from the text, so that it cannot terminate the sequence early and have the rest of it
+// interpreted by the terminal.
+fn osc_text(text: &str, w: &mut dyn io::Write) -> io::Result<Translation> {
+    let mut translation = Exact;
+    for c in text.chars() {
+        if c.is_control() {
+            translation = Approximate;
+        } else {
+            let mut buf = [0; 4];
+            w.write_all(c.encode_utf8(&mut buf).as_bytes())?;
+        }
+    }
+    w.write_all(b"\x1b\\")?;
+    Ok(translation)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use args::*;
+
+    use super::*;
+
+    fn ansi<C: Into<Command>>(cmd: C) -> (String, Translation) {
+        let mut buf = Vec::new();
+        let translation = translate(&cmd.into(), &mut buf).unwrap();
+        (String::from_utf8(buf).unwrap(), translation)
+    }
+
+    fn exact(s: &str) -> (String, Translation) {
+        (String::from(s), Exact)
+    }
+
+    #[test]
+    fn translate_movement() {
+        assert_eq!(ansi(Move::new(Movement::Position(Coords { x: 4, y: 0 }))), exact("\x1b[1;5H"));
+        assert_eq!(ansi(Move::new(Movement::To(Direction::Left, 3, false))), exact("\x1b[3D"));
+        assert_eq!(ansi(Move::new(Movement::To(Direction::Up, 0, false))), exact(""));
+        assert_eq!(ansi(Move::new(Movement::IndexTo(Direction::Down, 2))), exact("\x1bD\x1bD"));
+        assert_eq!(ansi(Move::new(Movement::NextLine(1))), exact("\r\x1bD"));
+        assert_eq!(ansi(Move::new(Movement::IndexTo(Direction::Up, u32::MAX))),
+                   exact(&"\x1bM".repeat(EDGE as usize)));
+        assert_eq!(ansi(Move::new(Movement::NextLine(u32::MAX))),
+                   exact(&format!("\r{}", "\x1bD".repeat(EDGE as usize))));
+        assert_eq!(ansi(Move::new(Movement::Column(9))), exact("\x1b[10G"));
+        assert_eq!(ansi(Move::new(Movement::Tab(Direction::Right, 1, true))),
+                   (String::from("\x1b[1I"), Approximate));
+        assert_eq!(ansi(Move::new(Movement::Tab(Direction::Left, 2, false))), exact("\x1b[2Z"));
+        assert_eq!(ansi(Move::new(Movement::Tab(Direction::Up, 2, true))), exact("\x1b[2A"));
+        assert_eq!(ansi(Move::new(Movement::Tab(Direction::Down, 1, false))), exact("\x1b[1B"));
+        assert_eq!(ansi(ScrollScreen::new(Direction::Down, 2)), exact("\x1b[2T"));
+    }
+
+    #[test]
+    fn translate_erase() {
+        assert_eq!(ansi(Erase::new(Area::WholeScreen)), exact("\x1b[2J"));
+        assert_eq!(ansi(Erase::new(Area::CursorTo(Movement::ToEdge(Direction::Right)))),
+                   exact("\x1b[K"));
+        assert_eq!(ansi(Erase::new(Area::Bound(Region::new(1, 2, 4, 4)))),
+                   exact("\x1b7\x1b[3;2H\x1b[3X\x1b[4;2H\x1b[3X\x1b8"));
+        let inverted = Region { left: 4, top: 4, right: 1, bottom: 2 };
+        assert_eq!(ansi(Erase::new(Area::Bound(inverted))), exact(""));
+        assert_eq!(ansi(Erase::new(Area::Bound(Region { right: 9, ..inverted }))), exact(""));
+        assert_eq!(ansi(Erase::new(Area::Rows(1, 2))), exact("\x1b7\x1b[2;1H\x1b[9999X\x1b8"));
+        assert_eq!(ansi(Erase::new(Area::Rows(3, 3))), exact(""));
+        assert_eq!(ansi(Erase::new(Area::Rows(3, 1))), exact(""));
+        assert_eq!(ansi(Erase::new(Area::Rows(EDGE - 1, u32::MAX))),
+                   exact("\x1b7\x1b[9999;1H\x1b[9999X\x1b8"));
+        assert_eq!(ansi(InsertRows::new(2, true)), exact("\x1b[2L"));
+        assert_eq!(ansi(RemoveChars::new(1)), exact("\x1b[1P"));
+    }
+
+    #[test]
+    fn translate_style() {
+        let style = |style: Style| ansi(SetTextStyle(vec![style]));
+        assert_eq!(style(Style::Bold(true)), exact("\x1b[1m"));
+        assert_eq!(style(Style::Underline(0)), exact("\x1b[24m"));
+        assert_eq!(style(Style::FgColor(Color::Palette(3))), exact("\x1b[33m"));
+        assert_eq!(style(Style::BgColor(Color::Palette(9))), exact("\x1b[101m"));
+        assert_eq!(style(Style::FgColor(Color::Palette(0x80))), exact("\x1b[38;5;128m"));
+        assert_eq!(style(Style::BgColor(Color::True(1, 2, 3))), exact("\x1b[48;2;1;2;3m"));
+        assert_eq!(style(Style::BgColor(Color::Default)), exact("\x1b[49m"));
+        assert_eq!(ansi(DefaultTextStyle), exact("\x1b[0m"));
+
+        // Styles set together are written in one sequence, leaving out those ANSI cannot express.
+        assert_eq!(ansi(SetTextStyle(vec![Style::Bold(true), Style::FgColor(Color::Palette(1)),
+                                          Style::BgColor(Color::Palette(0x80))])),
+                   exact("\x1b[1;31;48;5;128m"));
+        assert_eq!(ansi(SetTextStyle(vec![Style::Italic(true), Style::Opacity(0x80)])),
+                   (String::from("\x1b[3m"), Approximate));
+
+        assert_eq!(style(Style::UnderlineColor(Color::Palette(3))), exact("\x1b[58;5;3m"));
+        assert_eq!(style(Style::UnderlineColor(Color::True(1, 2, 3))), exact("\x1b[58;2;1;2;3m"));
+        assert_eq!(style(Style::UnderlineColor(Color::Default)), exact("\x1b[59m"));
+        assert_eq!(style(Style::UnderlineShape(UnderlineShape::Curly)),
+                   (String::new(), Unsupported));
+    }
+
+    #[test]
+    fn translate_title() {
+        assert_eq!(ansi(SetTitle(String::from("notty"))), exact("\x1b]2;notty\x1b\\"));
+        assert_eq!(ansi(SetTitle(String::from("a\x07b"))),
+                   (String::from("\x1b]2;ab\x1b\\"), Approximate));
+    }
+
+    #[test]
+    fn translate_hyperlinks() {
+        assert_eq!(ansi(BeginHyperlink::new(Some(0x10), String::from("https://example.com"))),
+                   exact("\x1b]8;id=16;https://example.com\x1b\\"));
+        assert_eq!(ansi(BeginHyperlink::new(None, String::from("file:///a\x1bb"))),
+                   (String::from("\x1b]8;;file:///ab\x1b\\"), Approximate));
+        assert_eq!(ansi(EndHyperlink), exact("\x1b]8;;\x1b\\"));
+        assert_eq!(ansi(AddHyperlink(Area::WholeScreen, String::from("a"))),
+                   (String::new(), Unsupported));
+    }
+
+    #[test]
+    fn unsupported_commands() {
+        assert_eq!(ansi(PushPanel(None, None)), (String::new(), Unsupported));
+        assert_eq!(ansi(RemoveToolTip(Coords { x: 0, y: 0 })), (String::new(), Unsupported));
+        assert_eq!(ansi(SetTextStyle(vec![Style::Opacity(0x80)])), (String::new(), Unsupported));
+        assert_eq!(ansi(Erase::new(Area::CursorColumn)), (String::new(), Unsupported));
+    }
+
+}
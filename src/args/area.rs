@@ -1,71 +1,272 @@
-use super::{Argument, Coords, Movement, Region};
-use self::Area::*;
+use std::cmp;
+use std::collections::BTreeSet;
+use std::slice;
+
+use super::{Coords, Movement, Region};
+use super::Direction::*;
+use super::Movement::*;
 
 /// An abstractly defined section of the grid.
 ///
 /// Areas can be defined in terms of the current cursor position and the bounds of the grid. They
-/// are converted into concrete sections of the screen when commands using Areas are applied.
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+/// are converted into concrete sections of the screen with `resolve` when commands using Areas
+/// are applied.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Argument)]
 pub enum Area {
     /// The cell the cursor is in.
+    #[tag = 1]
     CursorCell,
     /// The row the cursor is in.
+    #[tag = 2]
     CursorRow,
     /// The column the cursor is in.
+    #[tag = 3]
     CursorColumn,
     /// All cells the cursor would traverse through in performing a movement (including the cell
     /// the cursor is in now, and the cell it would end in).
+    #[tag = 4]
     CursorTo(Movement),
     /// The rectangle bound in one corner by the cursor position and another by this coordinate.
+    #[tag = 5]
     CursorBound(Coords),
     /// The entire screen.
+    #[tag = 6]
     WholeScreen,
     /// A concrete rectangular section of the screen.
+    ///
+    /// This used to share its tag with `WholeScreen`, as `6` followed by the region. It is now
+    /// encoded with tag `a`, but that older form still decodes as a `Bound`.
+    #[tag = 0xa]
+    #[legacy_tag = 6]
     Bound(Region),
     /// The rows between the two parameters, inclusive of the first but not the second.
-    Rows(u32, u32),
+    #[tag = 7]
+    Rows(#[name = "top"] u32, #[name = "bottom"] u32),
     /// The columns between the two parameters, inclusive of the first but not the second.
-    Columns(u32, u32),
+    #[tag = 8]
+    Columns(#[name = "left"] u32, #[name = "right"] u32),
     /// Everything below the row the cursor is in, the boolean determines if this is inclusive of
     /// the cursor or not (inclusive = true).
-    BelowCursor(bool),
+    #[tag = 9]
+    BelowCursor(#[default = true] bool),
+}
+
+
+impl Area {
+    /// Find the cells in this area, given the position of the cursor, the bounds of the grid and
+    /// its tab stops. Parts of the area which lie outside of the bounds are left out, and the
+    /// cursor is treated as being at the nearest coordinate within them. If the bounds are empty,
+    /// so is every area.
+    pub fn resolve(&self, cursor: Coords, bounds: Region, tab_stops: &BTreeSet<u32>) -> Cells {
+        if bounds.is_empty() { return Cells::default() }
+        let cursor = bounds.xy_within(cursor);
+        let Coords { x, y } = cursor;
+        let rect = |left, top, right, bottom| {
+            Region { left: left, top: top, right: right, bottom: bottom }.intersection(bounds)
+        };
+        let region = match *self {
+            Area::CursorCell            => rect(x, y, x + 1, y + 1),
+            Area::CursorRow             => rect(bounds.left, y, bounds.right, y + 1),
+            Area::CursorColumn          => rect(x, bounds.top, x + 1, bounds.bottom),
+            Area::CursorTo(movement)    => {
+                let (end, _) = movement.apply(cursor, bounds, tab_stops);
+                if vertical(movement) {
+                    rect(x, cmp::min(y, end.y), x + 1, cmp::max(y, end.y) + 1)
+                } else {
+                    // The cells between the cursor and the end of the movement in reading order.
+                    let (start, end) = if (end.y, end.x) < (y, x) { (end, cursor) }
+                                       else { (cursor, end) };
+                    if start.y == end.y {
+                        rect(start.x, start.y, end.x + 1, start.y + 1)
+                    } else {
+                        let regions = [
+                            rect(start.x, start.y, bounds.right, start.y + 1),
+                            rect(bounds.left, start.y + 1, bounds.right, end.y),
+                            rect(bounds.left, end.y, end.x + 1, end.y + 1),
+                        ];
+                        return Cells { regions: regions.iter().flat_map(|r| *r).collect() }
+                    }
+                }
+            }
+            Area::CursorBound(coords)   => {
+                let coords = bounds.xy_within(coords);
+                rect(cmp::min(x, coords.x), cmp::min(y, coords.y),
+                     cmp::max(x, coords.x) + 1, cmp::max(y, coords.y) + 1)
+            }
+            Area::WholeScreen           => Some(bounds),
+            Area::Bound(r)              => rect(r.left, r.top, r.right, r.bottom),
+            Area::Rows(top, bottom)     => rect(bounds.left, top, bounds.right, bottom),
+            Area::Columns(left, right)  => rect(left, bounds.top, right, bounds.bottom),
+            Area::BelowCursor(true)     => rect(bounds.left, y, bounds.right, bounds.bottom),
+            Area::BelowCursor(false)    => rect(bounds.left, y + 1, bounds.right, bounds.bottom),
+        };
+        Cells { regions: region.into_iter().collect() }
+    }
+}
+
+// Returns true if the cursor moves only within its column, so that the cells it traverses are
+// those in its column rather than those in reading order.
+fn vertical(movement: Movement) -> bool {
+    matches!(movement, To(Up | Down, ..) | ToEdge(Up | Down) | IndexTo(Up | Down, _)
+                       | Tab(Up | Down, ..) | Row(_))
 }
 
-impl Argument for Area {
-    fn from_nums<T>(mut args: T, default: Option<Area>) -> Option<Area>
-    where T: Iterator<Item=u64> {
-        match args.next() {
-            Some(1) => Some(CursorCell),
-            Some(2) => Some(CursorRow),
-            Some(3) => Some(CursorColumn),
-            Some(4) => Movement::from_nums(args, None).map(CursorTo),
-            Some(5) => Coords::from_nums(args, None).map(CursorBound),
-            Some(6) => Region::from_nums(args, None).map(Bound).or(Some(WholeScreen)),
-            Some(7) => match (args.next(), args.next()) {
-                (Some(top), Some(bottom))   => Some(Rows(top as u32, bottom as u32)),
-                _                           => Some(WholeScreen),
-            },
-            Some(8) => match (args.next(), args.next()) {
-                (Some(top), Some(bottom))   => Some(Columns(top as u32, bottom as u32)),
-                _                           => Some(WholeScreen),
-            },
-            Some(9) => bool::from_nums(args, Some(true)).map(BelowCursor),
-            _       => default,
+/// The concrete cells of an area, as resolved against a grid.
+///
+/// The cells are held as disjoint regions in reading order. Every area but `CursorTo` resolves
+/// to at most one region; a `CursorTo` area which spans several rows resolves to the rest of its
+/// first row, the rows in between and the start of its last row.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Cells {
+    regions: Vec<Region>,
+}
+
+impl Cells {
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    /// The region covered by these cells, if they are rectangular.
+    pub fn region(&self) -> Option<Region> {
+        let (first, last) = (*self.regions.first()?, *self.regions.last()?);
+        if self.regions.iter().all(|r| r.left == first.left && r.right == first.right) {
+            Some(Region { bottom: last.bottom, ..first })
+        } else {
+            None
         }
     }
 
-    fn encode(&self) -> String {
-        match *self {
-            CursorCell              => String::from("1"),
-            CursorRow               => String::from("2"),
-            CursorColumn            => String::from("3"),
-            CursorTo(mov)           => format!("4.{}", mov.encode()),
-            CursorBound(coords)     => format!("5.{}", coords.encode()),
-            WholeScreen             => format!("6"),
-            Bound(region)           => format!("6.{}", region.encode()),
-            Rows(top, bottom)       => format!("7.{:x}.{:x}", top, bottom),
-            Columns(left, right)    => format!("8.{:x}.{:x}", left, right),
-            BelowCursor(b)          => format!("9.{}", b.encode()),
+    pub fn contains(&self, coords: Coords) -> bool {
+        self.regions.iter().any(|region| region.contains(coords))
+    }
+
+    pub fn len(&self) -> usize {
+        self.regions.iter().map(|region| region.width() as usize * region.height() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    /// Iterate over the coordinates of the cells, in reading order.
+    pub fn iter(&self) -> CellsIter<'_> {
+        CellsIter { regions: self.regions.iter(), current: None }
+    }
+}
+
+impl<'a> IntoIterator for &'a Cells {
+    type Item = Coords;
+    type IntoIter = CellsIter<'a>;
+
+    fn into_iter(self) -> CellsIter<'a> {
+        self.iter()
+    }
+}
+
+/// An iterator over the coordinates of a set of `Cells`.
+#[derive(Clone, Debug)]
+pub struct CellsIter<'a> {
+    regions: slice::Iter<'a, Region>,
+    // The region being iterated over, and the next coordinates in it.
+    current: Option<(Region, Coords)>,
+}
+
+impl<'a> Iterator for CellsIter<'a> {
+    type Item = Coords;
+
+    fn next(&mut self) -> Option<Coords> {
+        loop {
+            if let Some((region, coords)) = self.current {
+                let next = if coords.x + 1 < region.right {
+                    Coords { x: coords.x + 1, ..coords }
+                } else {
+                    Coords { x: region.left, y: coords.y + 1 }
+                };
+                self.current = if next.y < region.bottom { Some((region, next)) } else { None };
+                return Some(coords)
+            }
+            let region = *self.regions.next()?;
+            self.current = Some((region, Coords { x: region.left, y: region.top }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::BTreeSet;
+
+    use args::*;
+
+    use super::Cells;
+
+    static BOUNDS: Region = Region { left: 0, top: 0, right: 8, bottom: 4 };
+
+    fn resolve(area: Area, x: u32, y: u32) -> Cells {
+        let tab_stops: BTreeSet<u32> = [4].iter().cloned().collect();
+        area.resolve(Coords { x: x, y: y }, BOUNDS, &tab_stops)
+    }
+
+    fn coords(cells: &Cells) -> Vec<(u32, u32)> {
+        cells.iter().map(|Coords { x, y }| (x, y)).collect()
+    }
+
+    #[test]
+    fn rectangular_areas() {
+        let areas = [
+            (Area::CursorCell, Region::new(2, 1, 3, 2)),
+            (Area::CursorRow, Region::new(0, 1, 8, 2)),
+            (Area::CursorColumn, Region::new(2, 0, 3, 4)),
+            (Area::CursorBound(Coords { x: 0, y: 3 }), Region::new(0, 1, 3, 4)),
+            (Area::CursorBound(Coords { x: 20, y: 0 }), Region::new(2, 0, 8, 2)),
+            (Area::WholeScreen, BOUNDS),
+            (Area::Bound(Region::new(6, 2, 10, 10)), Region::new(6, 2, 8, 4)),
+            (Area::Rows(2, 9), Region::new(0, 2, 8, 4)),
+            (Area::Columns(1, 3), Region::new(1, 0, 3, 4)),
+            (Area::BelowCursor(true), Region::new(0, 1, 8, 4)),
+            (Area::BelowCursor(false), Region::new(0, 2, 8, 4)),
+            (Area::CursorTo(Movement::ToEdge(Direction::Up)), Region::new(2, 0, 3, 2)),
+            (Area::CursorTo(Movement::Tab(Direction::Right, 1, false)), Region::new(2, 1, 5, 2)),
+            (Area::CursorTo(Movement::Column(0)), Region::new(0, 1, 3, 2)),
+        ];
+        for &(area, region) in &areas {
+            let cells = resolve(area, 2, 1);
+            assert_eq!(cells.region(), Some(region), "{:?}", area);
+            assert_eq!(cells.len(), (region.width() * region.height()) as usize);
+        }
+    }
+
+    #[test]
+    fn empty_areas() {
+        for &area in &[Area::Rows(4, 8), Area::Columns(3, 3), Area::BelowCursor(false)] {
+            let cells = resolve(area, 2, 3);
+            assert!(cells.is_empty(), "{:?}", area);
+            assert_eq!(cells.iter().next(), None);
+        }
+        let empty = Region { left: 0, top: 0, right: 0, bottom: 0 };
+        for &area in &[Area::CursorRow, Area::WholeScreen, Area::CursorTo(Movement::ToEnd)] {
+            let cells = area.resolve(Coords { x: 2, y: 3 }, empty, &BTreeSet::new());
+            assert!(cells.is_empty(), "{:?}", area);
         }
     }
+
+    #[test]
+    fn traversed_cells() {
+        let cells = resolve(Area::CursorTo(Movement::To(Direction::Right, 8, true)), 6, 1);
+        assert_eq!(cells.regions(), [Region::new(6, 1, 8, 2), Region::new(0, 2, 7, 3)]);
+        assert_eq!(cells.region(), None);
+        assert_eq!(coords(&cells), [(6, 1), (7, 1), (0, 2), (1, 2), (2, 2), (3, 2), (4, 2), (5, 2),
+                                    (6, 2)]);
+        assert!(cells.contains(Coords { x: 3, y: 2 }));
+        assert!(!cells.contains(Coords { x: 3, y: 1 }));
+
+        let cells = resolve(Area::CursorTo(Movement::ToBeginning), 1, 2);
+        assert_eq!(cells.regions(), [Region::new(0, 0, 8, 1), Region::new(0, 1, 8, 2),
+                                     Region::new(0, 2, 2, 3)]);
+        assert_eq!(cells.len(), 18);
+
+        let cells = resolve(Area::CursorTo(Movement::Position(Coords { x: 7, y: 3 })), 0, 0);
+        assert_eq!(cells.region(), Some(BOUNDS));
+        assert_eq!(coords(&cells).last(), Some(&(7, 3)));
+    }
 }
@@ -1,40 +1,308 @@
-use super::Argument;
+use std::str::FromStr;
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+use ParseColorError;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Argument)]
 pub enum Color {
     /// The default color for this item.
+    #[default]
+    #[tag = 0]
     Default,
     /// An index into the 256-member provided color palette.
-    Palette(u8),
+    #[tag = 1]
+    Palette(#[name = "palette"] u8),
     /// A 24-bit rgb color triple.
-    True(u8, u8, u8),
+    #[tag = 2]
+    True(#[name = "red"] u8, #[name = "green"] u8, #[name = "blue"] u8),
 }
 
-impl Default for Color {
-    fn default() -> Color {
-        Color::Default
+impl Color {
+    /// The red, green and blue components of this color. Palette colors are looked up in the
+    /// standard xterm palette; the default color has no components.
+    pub fn rgb(&self) -> Option<(u8, u8, u8)> {
+        match *self {
+            Color::Default          => None,
+            Color::Palette(n)       => Some(palette_rgb(n)),
+            Color::True(r, g, b)    => Some((r, g, b)),
+        }
     }
-}
 
-impl Argument for Color {
-    fn from_nums<T>(mut args: T, default: Option<Color>) -> Option<Color>
-    where T: Iterator<Item=u64> {
-        match args.next() {
-            Some(0) => Some(Color::Default),
-            Some(1) => args.next().map(|n| Color::Palette(n as u8)),
-            Some(2) => match (args.next(), args.next(), args.next()) {
-                (Some(r), Some(g), Some(b)) => Some(Color::True(r as u8, g as u8, b as u8)),
-                _                           => None
-            },
-            _       => default
+    /// The closest color in a 256 color palette. True colors are matched to the 6x6x6 color cube
+    /// and the gray ramp of the xterm palette, but not to its first 16 colors, which terminals
+    /// often change to match a theme.
+    pub fn to_256_colors(&self) -> Color {
+        match *self {
+            Color::True(r, g, b)    => nearest((r, g, b), 16..=255),
+            color                   => color,
         }
     }
 
-    fn encode(&self) -> String {
+    /// The closest of the 16 basic colors.
+    pub fn to_16_colors(&self) -> Color {
         match *self {
-            Color::Default          => String::from("0"),
-            Color::Palette(n)       => format!("1.{:x}", n),
-            Color::True(r, g, b)    => format!("2.{:x}.{:x}.{:x}", r, g, b)
+            Color::Palette(n) if n < 16 => Color::Palette(n),
+            Color::Default              => Color::Default,
+            color                       => nearest(color.rgb().unwrap_or_default(), 0..=15),
         }
     }
+
+    /// A true color from a hue in degrees and a saturation and lightness between 0 and 1.
+    pub fn from_hsl(hue: f32, saturation: f32, lightness: f32) -> Color {
+        let (s, l) = (saturation.clamp(0.0, 1.0), lightness.clamp(0.0, 1.0));
+        let h = hue.rem_euclid(360.0) / 60.0;
+        let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as u32 {
+            0   => (chroma, x, 0.0),
+            1   => (x, chroma, 0.0),
+            2   => (0.0, chroma, x),
+            3   => (0.0, x, chroma),
+            4   => (x, 0.0, chroma),
+            _   => (chroma, 0.0, x),
+        };
+        let m = l - chroma / 2.0;
+        let byte = |c: f32| ((c + m) * 255.0).round() as u8;
+        Color::True(byte(r), byte(g), byte(b))
+    }
+
+    /// The hue in degrees, and the saturation and lightness between 0 and 1, of this color.
+    pub fn hsl(&self) -> Option<(f32, f32, f32)> {
+        let (r, g, b) = self.rgb()?;
+        let (r, g, b) = (r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0);
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let (chroma, l) = (max - min, (max + min) / 2.0);
+        if chroma == 0.0 { return Some((0.0, 0.0, l)) }
+        let h = if max == r {
+            ((g - b) / chroma).rem_euclid(6.0)
+        } else if max == g {
+            (b - r) / chroma + 2.0
+        } else {
+            (r - g) / chroma + 4.0
+        };
+        Some((h * 60.0, chroma / (1.0 - (2.0 * l - 1.0).abs()), l))
+    }
+
+    /// Mix this color with another, by a fraction between 0 (this color) and 1 (the other). The
+    /// colors are mixed in linear RGB, so that the result is as bright as it appears it should
+    /// be. The default color cannot be mixed.
+    pub fn blend(&self, other: Color, fraction: f32) -> Option<Color> {
+        let (from, to) = (self.rgb()?, other.rgb()?);
+        let t = fraction.clamp(0.0, 1.0);
+        let mix = |a: u8, b: u8| {
+            let (a, b) = (to_linear(a), to_linear(b));
+            from_linear(a + (b - a) * t)
+        };
+        Some(Color::True(mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2)))
+    }
+}
+
+/// Colors are parsed from `#rrggbb`, `#rgb`, a CSS color name or `default`, ignoring case.
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    fn from_str(s: &str) -> Result<Color, ParseColorError> {
+        let error = || ParseColorError(String::from(s));
+        let name = s.to_ascii_lowercase();
+        if name == "default" { return Ok(Color::Default) }
+        if let Some(hex) = name.strip_prefix('#') {
+            if !hex.bytes().all(|b| b.is_ascii_hexdigit()) { return Err(error()) }
+            let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).unwrap_or(0);
+            return match hex.len() {
+                6   => Ok(Color::True(digit(0) << 4 | digit(1), digit(2) << 4 | digit(3),
+                                      digit(4) << 4 | digit(5))),
+                3   => Ok(Color::True(digit(0) * 0x11, digit(1) * 0x11, digit(2) * 0x11)),
+                _   => Err(error()),
+            }
+        }
+        CSS_COLORS.binary_search_by(|&(css, _)| css.cmp(&name))
+                  .map(|i| {
+                      let (r, g, b) = CSS_COLORS[i].1;
+                      Color::True(r, g, b)
+                  })
+                  .map_err(|_| error())
+    }
+}
+
+// The color of an entry in the xterm palette: the 16 basic colors, then a 6x6x6 color cube,
+// then a ramp of 24 grays from dark to light.
+fn palette_rgb(n: u8) -> (u8, u8, u8) {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    match n {
+        0..=15      => BASIC_COLORS[n as usize],
+        16..=231    => {
+            let n = n - 16;
+            (LEVELS[(n / 36) as usize], LEVELS[(n / 6 % 6) as usize], LEVELS[(n % 6) as usize])
+        }
+        _           => {
+            let gray = 8 + (n - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
+// The palette color in a range closest to a color, by distance in RGB. Ties go to the first.
+fn nearest<R>((r, g, b): (u8, u8, u8), range: R) -> Color
+where R: Iterator<Item=u8> {
+    let distance = |n: &u8| {
+        let (pr, pg, pb) = palette_rgb(*n);
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(r, pr) + d(g, pg) + d(b, pb)
+    };
+    range.min_by_key(distance).map(Color::Palette).unwrap_or_default()
+}
+
+fn to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.0;
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+fn from_linear(c: f32) -> u8 {
+    let c = if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+    (c * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+// The colors xterm uses for the 16 basic colors by default.
+const BASIC_COLORS: [(u8, u8, u8); 16] = [
+    (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0),
+    (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+    (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0),
+    (92, 92, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255),
+];
+
+// The named colors of CSS, sorted by name.
+const CSS_COLORS: &'static [(&'static str, (u8, u8, u8))] = &[
+    ("aliceblue", (240, 248, 255)), ("antiquewhite", (250, 235, 215)), ("aqua", (0, 255, 255)),
+    ("aquamarine", (127, 255, 212)), ("azure", (240, 255, 255)), ("beige", (245, 245, 220)),
+    ("bisque", (255, 228, 196)), ("black", (0, 0, 0)), ("blanchedalmond", (255, 235, 205)),
+    ("blue", (0, 0, 255)), ("blueviolet", (138, 43, 226)), ("brown", (165, 42, 42)),
+    ("burlywood", (222, 184, 135)), ("cadetblue", (95, 158, 160)), ("chartreuse", (127, 255, 0)),
+    ("chocolate", (210, 105, 30)), ("coral", (255, 127, 80)), ("cornflowerblue", (100, 149, 237)),
+    ("cornsilk", (255, 248, 220)), ("crimson", (220, 20, 60)), ("cyan", (0, 255, 255)),
+    ("darkblue", (0, 0, 139)), ("darkcyan", (0, 139, 139)), ("darkgoldenrod", (184, 134, 11)),
+    ("darkgray", (169, 169, 169)), ("darkgreen", (0, 100, 0)), ("darkgrey", (169, 169, 169)),
+    ("darkkhaki", (189, 183, 107)), ("darkmagenta", (139, 0, 139)),
+    ("darkolivegreen", (85, 107, 47)), ("darkorange", (255, 140, 0)),
+    ("darkorchid", (153, 50, 204)), ("darkred", (139, 0, 0)), ("darksalmon", (233, 150, 122)),
+    ("darkseagreen", (143, 188, 143)), ("darkslateblue", (72, 61, 139)),
+    ("darkslategray", (47, 79, 79)), ("darkslategrey", (47, 79, 79)),
+    ("darkturquoise", (0, 206, 209)), ("darkviolet", (148, 0, 211)), ("deeppink", (255, 20, 147)),
+    ("deepskyblue", (0, 191, 255)), ("dimgray", (105, 105, 105)), ("dimgrey", (105, 105, 105)),
+    ("dodgerblue", (30, 144, 255)), ("firebrick", (178, 34, 34)), ("floralwhite", (255, 250, 240)),
+    ("forestgreen", (34, 139, 34)), ("fuchsia", (255, 0, 255)), ("gainsboro", (220, 220, 220)),
+    ("ghostwhite", (248, 248, 255)), ("gold", (255, 215, 0)), ("goldenrod", (218, 165, 32)),
+    ("gray", (128, 128, 128)), ("green", (0, 128, 0)), ("greenyellow", (173, 255, 47)),
+    ("grey", (128, 128, 128)), ("honeydew", (240, 255, 240)), ("hotpink", (255, 105, 180)),
+    ("indianred", (205, 92, 92)), ("indigo", (75, 0, 130)), ("ivory", (255, 255, 240)),
+    ("khaki", (240, 230, 140)), ("lavender", (230, 230, 250)), ("lavenderblush", (255, 240, 245)),
+    ("lawngreen", (124, 252, 0)), ("lemonchiffon", (255, 250, 205)), ("lightblue", (173, 216, 230)),
+    ("lightcoral", (240, 128, 128)), ("lightcyan", (224, 255, 255)),
+    ("lightgoldenrodyellow", (250, 250, 210)), ("lightgray", (211, 211, 211)),
+    ("lightgreen", (144, 238, 144)), ("lightgrey", (211, 211, 211)), ("lightpink", (255, 182, 193)),
+    ("lightsalmon", (255, 160, 122)), ("lightseagreen", (32, 178, 170)),
+    ("lightskyblue", (135, 206, 250)), ("lightslategray", (119, 136, 153)),
+    ("lightslategrey", (119, 136, 153)), ("lightsteelblue", (176, 196, 222)),
+    ("lightyellow", (255, 255, 224)), ("lime", (0, 255, 0)), ("limegreen", (50, 205, 50)),
+    ("linen", (250, 240, 230)), ("magenta", (255, 0, 255)), ("maroon", (128, 0, 0)),
+    ("mediumaquamarine", (102, 205, 170)), ("mediumblue", (0, 0, 205)),
+    ("mediumorchid", (186, 85, 211)), ("mediumpurple", (147, 112, 219)),
+    ("mediumseagreen", (60, 179, 113)), ("mediumslateblue", (123, 104, 238)),
+    ("mediumspringgreen", (0, 250, 154)), ("mediumturquoise", (72, 209, 204)),
+    ("mediumvioletred", (199, 21, 133)), ("midnightblue", (25, 25, 112)),
+    ("mintcream", (245, 255, 250)), ("mistyrose", (255, 228, 225)), ("moccasin", (255, 228, 181)),
+    ("navajowhite", (255, 222, 173)), ("navy", (0, 0, 128)), ("oldlace", (253, 245, 230)),
+    ("olive", (128, 128, 0)), ("olivedrab", (107, 142, 35)), ("orange", (255, 165, 0)),
+    ("orangered", (255, 69, 0)), ("orchid", (218, 112, 214)), ("palegoldenrod", (238, 232, 170)),
+    ("palegreen", (152, 251, 152)), ("paleturquoise", (175, 238, 238)),
+    ("palevioletred", (219, 112, 147)), ("papayawhip", (255, 239, 213)),
+    ("peachpuff", (255, 218, 185)), ("peru", (205, 133, 63)), ("pink", (255, 192, 203)),
+    ("plum", (221, 160, 221)), ("powderblue", (176, 224, 230)), ("purple", (128, 0, 128)),
+    ("rebeccapurple", (102, 51, 153)), ("red", (255, 0, 0)), ("rosybrown", (188, 143, 143)),
+    ("royalblue", (65, 105, 225)), ("saddlebrown", (139, 69, 19)), ("salmon", (250, 128, 114)),
+    ("sandybrown", (244, 164, 96)), ("seagreen", (46, 139, 87)), ("seashell", (255, 245, 238)),
+    ("sienna", (160, 82, 45)), ("silver", (192, 192, 192)), ("skyblue", (135, 206, 235)),
+    ("slateblue", (106, 90, 205)), ("slategray", (112, 128, 144)), ("slategrey", (112, 128, 144)),
+    ("snow", (255, 250, 250)), ("springgreen", (0, 255, 127)), ("steelblue", (70, 130, 180)),
+    ("tan", (210, 180, 140)), ("teal", (0, 128, 128)), ("thistle", (216, 191, 216)),
+    ("tomato", (255, 99, 71)), ("turquoise", (64, 224, 208)), ("violet", (238, 130, 238)),
+    ("wheat", (245, 222, 179)), ("white", (255, 255, 255)), ("whitesmoke", (245, 245, 245)),
+    ("yellow", (255, 255, 0)), ("yellowgreen", (154, 205, 50)),
+];
+
+#[cfg(test)]
+mod tests {
+
+    use args::Color;
+    use ParseColorError;
+
+    #[test]
+    fn palette_colors() {
+        assert_eq!(Color::Default.rgb(), None);
+        assert_eq!(Color::Palette(1).rgb(), Some((205, 0, 0)));
+        assert_eq!(Color::Palette(16).rgb(), Some((0, 0, 0)));
+        assert_eq!(Color::Palette(196).rgb(), Some((255, 0, 0)));
+        assert_eq!(Color::Palette(110).rgb(), Some((135, 175, 215)));
+        assert_eq!(Color::Palette(232).rgb(), Some((8, 8, 8)));
+        assert_eq!(Color::Palette(255).rgb(), Some((238, 238, 238)));
+        assert_eq!(Color::True(1, 2, 3).rgb(), Some((1, 2, 3)));
+    }
+
+    #[test]
+    fn nearest_colors() {
+        assert_eq!(Color::True(255, 0, 0).to_256_colors(), Color::Palette(196));
+        assert_eq!(Color::True(130, 170, 220).to_256_colors(), Color::Palette(110));
+        assert_eq!(Color::True(100, 100, 100).to_256_colors(), Color::Palette(241));
+        assert_eq!(Color::True(0, 0, 0).to_256_colors(), Color::Palette(16));
+        assert_eq!(Color::Palette(3).to_256_colors(), Color::Palette(3));
+        assert_eq!(Color::Default.to_256_colors(), Color::Default);
+
+        assert_eq!(Color::True(250, 10, 10).to_16_colors(), Color::Palette(9));
+        assert_eq!(Color::True(0, 0, 200).to_16_colors(), Color::Palette(4));
+        assert_eq!(Color::Palette(196).to_16_colors(), Color::Palette(9));
+        assert_eq!(Color::Palette(244).to_16_colors(), Color::Palette(8));
+        assert_eq!(Color::Palette(12).to_16_colors(), Color::Palette(12));
+        assert_eq!(Color::Default.to_16_colors(), Color::Default);
+    }
+
+    #[test]
+    fn parsing_colors() {
+        assert_eq!("#ff8000".parse(), Ok(Color::True(0xff, 0x80, 0)));
+        assert_eq!("#FF8000".parse(), Ok(Color::True(0xff, 0x80, 0)));
+        assert_eq!("#f80".parse(), Ok(Color::True(0xff, 0x88, 0)));
+        assert_eq!("rebeccapurple".parse(), Ok(Color::True(0x66, 0x33, 0x99)));
+        assert_eq!("AliceBlue".parse(), Ok(Color::True(0xf0, 0xf8, 0xff)));
+        assert_eq!("default".parse(), Ok(Color::Default));
+        for &bad in &["", "#", "#ff80", "#ff800g", "#+f8000", "notacolor", "ff8000"] {
+            assert_eq!(bad.parse::<Color>(), Err(ParseColorError(String::from(bad))));
+        }
+    }
+
+    #[test]
+    fn hsl_colors() {
+        assert_eq!(Color::from_hsl(0.0, 1.0, 0.5), Color::True(255, 0, 0));
+        assert_eq!(Color::from_hsl(120.0, 1.0, 0.25), Color::True(0, 128, 0));
+        assert_eq!(Color::from_hsl(-120.0, 1.0, 0.5), Color::True(0, 0, 255));
+        assert_eq!(Color::from_hsl(270.0, 0.5, 0.4), Color::True(102, 51, 153));
+        assert_eq!(Color::from_hsl(42.0, 0.0, 1.0), Color::True(255, 255, 255));
+        assert_eq!(Color::Default.hsl(), None);
+        assert_eq!(Color::True(128, 128, 128).hsl().map(|(h, s, _)| (h, s)), Some((0.0, 0.0)));
+        for &color in &[Color::True(102, 51, 153), Color::True(10, 200, 30), Color::Palette(1),
+                        Color::True(255, 105, 180)] {
+            let (h, s, l) = color.hsl().unwrap();
+            assert_eq!(Color::from_hsl(h, s, l).rgb(), color.rgb(), "{:?}", color);
+        }
+    }
+
+    #[test]
+    fn blending_colors() {
+        let (black, white) = (Color::True(0, 0, 0), Color::True(255, 255, 255));
+        assert_eq!(black.blend(white, 0.0), Some(black));
+        assert_eq!(black.blend(white, 1.0), Some(white));
+        // Half of the light of white is much brighter than half of its sRGB value.
+        assert_eq!(black.blend(white, 0.5), Some(Color::True(188, 188, 188)));
+        assert_eq!(Color::Palette(9).blend(Color::True(0, 0, 255), 0.5),
+                   Some(Color::True(188, 0, 188)));
+        assert_eq!(black.blend(Color::Default, 0.5), None);
+        assert_eq!(Color::Default.blend(white, 0.5), None);
+    }
 }
@@ -0,0 +1,172 @@
+use std::convert::TryFrom;
+use std::io;
+
+use error::{DecodeError, ErrorKind};
+
+use super::{Argument, Fields};
+
+/// `None` is encoded as `0`, and `Some` as `1` followed by the fields of its value.
+///
+/// Unlike the optional arguments of a command, which are left out entirely when they are absent,
+/// an optional field can appear anywhere inside of another argument.
+impl<T: Argument> Argument for Option<T> {
+    fn from_nums<I>(mut args: I, default: Option<Option<T>>) -> Option<Option<T>>
+    where I: Iterator<Item=u64> {
+        match args.next() {
+            Some(0) => Some(None),
+            Some(1) => T::from_nums(args, None).map(Some).or(default),
+            _       => default,
+        }
+    }
+
+    fn encode_to<W>(&self, w: &mut W) -> io::Result<()>
+    where W: io::Write + ?Sized {
+        match *self {
+            Some(ref value) => {
+                w.write_all(b"1.")?;
+                value.encode_to(w)
+            }
+            None            => w.write_all(b"0"),
+        }
+    }
+
+    fn read(fields: &mut Fields) -> Result<Option<T>, DecodeError> {
+        Self::read_named(fields, "value")
+    }
+
+    fn read_named(fields: &mut Fields, name: &'static str) -> Result<Option<T>, DecodeError> {
+        match fields.require()? {
+            0   => Ok(None),
+            1   => T::read_named(fields, name).map(Some),
+            _   => Err(fields.error(ErrorKind::OutOfRange)),
+        }
+    }
+}
+
+// Tuples are encoded as their elements in order, separated by `.`, in the same way as a struct.
+macro_rules! tuple {
+    ($head:ident $head_value:ident $(, $t:ident $value:ident)*) => {
+        impl<$head: Argument $(, $t: Argument)*> Argument for ($head, $($t,)*) {
+            fn from_nums<I>(mut args: I, default: Option<Self>) -> Option<Self>
+            where I: Iterator<Item=u64> {
+                Some((
+                    match $head::from_nums(args.by_ref(), None) {
+                        Some(value) => value,
+                        None        => return default,
+                    },
+                    $(match $t::from_nums(args.by_ref(), None) {
+                        Some(value) => value,
+                        None        => return default,
+                    },)*
+                ))
+            }
+
+            fn encode_to<W>(&self, w: &mut W) -> io::Result<()>
+            where W: io::Write + ?Sized {
+                let (ref $head_value, $(ref $value,)*) = *self;
+                $head_value.encode_to(w)?;
+                $(
+                    w.write_all(b".")?;
+                    $value.encode_to(w)?;
+                )*
+                Ok(())
+            }
+
+            fn read(fields: &mut Fields) -> Result<Self, DecodeError> {
+                Ok(($head::read(fields)?, $($t::read(fields)?,)*))
+            }
+        }
+    }
+}
+
+tuple!(A a);
+tuple!(A a, B b);
+tuple!(A a, B b, C c);
+tuple!(A a, B b, C c, D d);
+tuple!(A a, B b, C c, D d, E e);
+tuple!(A a, B b, C c, D d, E e, F f);
+
+/// Arrays are encoded as their elements in order, separated by `.`. An empty array has no
+/// fields, so it cannot be encoded inside of another argument.
+impl<T: Argument, const N: usize> Argument for [T; N] {
+    fn from_nums<I>(mut args: I, default: Option<[T; N]>) -> Option<[T; N]>
+    where I: Iterator<Item=u64> {
+        let mut values = Vec::with_capacity(N);
+        for _ in 0..N {
+            match T::from_nums(args.by_ref(), None) {
+                Some(value) => values.push(value),
+                None        => return default,
+            }
+        }
+        <[T; N]>::try_from(values).ok().or(default)
+    }
+
+    fn encode_to<W>(&self, w: &mut W) -> io::Result<()>
+    where W: io::Write + ?Sized {
+        for (i, value) in self.iter().enumerate() {
+            if i > 0 { w.write_all(b".")?; }
+            value.encode_to(w)?;
+        }
+        Ok(())
+    }
+
+    fn read(fields: &mut Fields) -> Result<[T; N], DecodeError> {
+        Self::read_named(fields, "value")
+    }
+
+    fn read_named(fields: &mut Fields, name: &'static str) -> Result<[T; N], DecodeError> {
+        let mut values = Vec::with_capacity(N);
+        for _ in 0..N {
+            values.push(T::read_named(fields, name)?);
+        }
+        <[T; N]>::try_from(values).map_err(|_| DecodeError::new(ErrorKind::Missing))
+    }
+}
+
+/// Lists are encoded as their length, followed by each of their elements.
+///
+/// The length is not trusted when decoding: the list ends with an error as soon as the fields of
+/// the argument run out, whatever length was given.
+impl<T: Argument> Argument for Vec<T> {
+    fn from_nums<I>(mut args: I, default: Option<Vec<T>>) -> Option<Vec<T>>
+    where I: Iterator<Item=u64> {
+        let len = match args.next() {
+            Some(len)   => len,
+            None        => return default,
+        };
+        let mut args = args.peekable();
+        let mut values = Vec::new();
+        for _ in 0..len {
+            if args.peek().is_none() { return default }
+            match T::from_nums(args.by_ref(), None) {
+                Some(value) => values.push(value),
+                None        => return default,
+            }
+        }
+        Some(values)
+    }
+
+    fn encode_to<W>(&self, w: &mut W) -> io::Result<()>
+    where W: io::Write + ?Sized {
+        write!(w, "{:x}", self.len())?;
+        for value in self {
+            w.write_all(b".")?;
+            value.encode_to(w)?;
+        }
+        Ok(())
+    }
+
+    fn read(fields: &mut Fields) -> Result<Vec<T>, DecodeError> {
+        Self::read_named(fields, "value")
+    }
+
+    fn read_named(fields: &mut Fields, name: &'static str) -> Result<Vec<T>, DecodeError> {
+        let len = fields.require()?;
+        let mut values = Vec::new();
+        for _ in 0..len {
+            if fields.is_empty() { return Err(DecodeError::new(ErrorKind::Missing)) }
+            values.push(T::read_named(fields, name)?);
+        }
+        Ok(values)
+    }
+}
@@ -1,12 +1,14 @@
-use super::Argument;
-use self::Direction::*;
 
 /// A direction of movement across the grid.
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Argument)]
 pub enum Direction {
+    #[tag = 1]
     Up,
+    #[tag = 2]
     Down,
+    #[tag = 3]
     Left,
+    #[tag = 4]
     Right,
 }
 
@@ -21,24 +23,3 @@ impl Direction {
     }
 }
 
-impl Argument for Direction {
-    fn from_nums<T>(mut args: T, default: Option<Direction>) -> Option<Direction>
-    where T: Iterator<Item=u64> {
-        match args.next() {
-            Some(1) => Some(Up),
-            Some(2) => Some(Down),
-            Some(3) => Some(Left),
-            Some(4) => Some(Right),
-            _       => default
-        }
-    }
-
-    fn encode(&self) -> String {
-        match *self {
-            Up      => String::from("1"),
-            Down    => String::from("2"),
-            Left    => String::from("3"),
-            Right   => String::from("4"),
-        }
-    }
-}
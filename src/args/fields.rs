@@ -0,0 +1,106 @@
+use std::iter::Peekable;
+use std::num::IntErrorKind;
+use std::str::Split;
+
+use error::{DecodeError, ErrorKind};
+
+/// A reader over the `.` separated hexadecimal fields of an encoded argument.
+///
+/// In strict mode, values which are too large for the field they are read into are rejected;
+/// otherwise they are truncated to fit, as `Argument::decode` does.
+pub struct Fields<'a> {
+    fields: Option<Peekable<Split<'a, char>>>,
+    last: &'a str,
+    strict: bool,
+}
+
+impl<'a> Fields<'a> {
+    pub fn new(arg: &'a str) -> Fields<'a> {
+        Fields {
+            fields: if arg.is_empty() { None } else { Some(arg.split('.').peekable()) },
+            last: "",
+            strict: false,
+        }
+    }
+
+    /// Create a reader in strict mode.
+    pub fn strict(arg: &'a str) -> Fields<'a> {
+        Fields { strict: true, ..Fields::new(arg) }
+    }
+
+    /// Returns true if there are no more fields to read.
+    pub fn is_empty(&mut self) -> bool {
+        self.fields.as_mut().is_none_or(|fields| fields.peek().is_none())
+    }
+
+    /// Read the next field, if there is one.
+    pub fn next_field(&mut self) -> Result<Option<u64>, DecodeError> {
+        match self.fields.as_mut().and_then(Iterator::next) {
+            Some(field) => {
+                self.last = field;
+                u64::from_str_radix(field, 16).map(Some).map_err(|err| {
+                    let kind = match *err.kind() {
+                        IntErrorKind::PosOverflow   => ErrorKind::Overflow,
+                        _                           => ErrorKind::InvalidDigit,
+                    };
+                    DecodeError::new(kind).with_token(field)
+                })
+            }
+            None        => Ok(None),
+        }
+    }
+
+    /// Read the next field, returning an error if there is none.
+    pub fn require(&mut self) -> Result<u64, DecodeError> {
+        self.next_field()?.ok_or_else(|| DecodeError::new(ErrorKind::Missing))
+    }
+
+    /// Read the next field into a `u8` field with the given name.
+    pub fn read_u8(&mut self, field: &'static str) -> Result<u8, DecodeError> {
+        let n = self.require()?;
+        self.narrow(n, u8::MAX as u64, field).map(|n| n as u8)
+    }
+
+    /// Read the next field into a `u16` field with the given name.
+    pub fn read_u16(&mut self, field: &'static str) -> Result<u16, DecodeError> {
+        let n = self.require()?;
+        self.narrow(n, u16::MAX as u64, field).map(|n| n as u16)
+    }
+
+    /// Read the next field into a `u32` field with the given name.
+    pub fn read_u32(&mut self, field: &'static str) -> Result<u32, DecodeError> {
+        let n = self.require()?;
+        self.narrow(n, u32::MAX as u64, field).map(|n| n as u32)
+    }
+
+    /// Check that the most recently read value fits into a field whose maximum value is `max`.
+    /// In strict mode, returns an error naming the field if it does not.
+    pub fn narrow(&self, n: u64, max: u64, field: &'static str) -> Result<u64, DecodeError> {
+        if self.strict && n > max {
+            Err(self.error(ErrorKind::Overflow).in_field(field))
+        } else { Ok(n) }
+    }
+
+    /// Check that the most recently read signed value fits into a field which holds values from
+    /// `min` to `max`. In strict mode, returns an error naming the field if it does not;
+    /// otherwise, values out of range saturate to `min` or `max`.
+    pub fn narrow_signed(&self, n: i64, min: i64, max: i64, field: &'static str)
+            -> Result<i64, DecodeError> {
+        if self.strict && (n < min || n > max) {
+            Err(self.error(ErrorKind::Overflow).in_field(field))
+        } else { Ok(n.clamp(min, max)) }
+    }
+
+    /// Returns an error of the given kind, caused by the most recently read field.
+    pub fn error(&self, kind: ErrorKind) -> DecodeError {
+        DecodeError::new(kind).with_token(self.last)
+    }
+
+    /// Returns an error if any fields have not been read.
+    pub fn finish(mut self) -> Result<(), DecodeError> {
+        match self.fields.as_mut().and_then(Iterator::next) {
+            Some(field) => Err(DecodeError::new(ErrorKind::TrailingFields).with_token(field)),
+            None        => Ok(()),
+        }
+    }
+}
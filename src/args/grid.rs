@@ -1,76 +1,30 @@
-use super::Argument;
-
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+/// How a grid is fit into a region of a different size, and how the position of a split is read.
+/// See `Region::split`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Argument)]
 pub enum ResizeRule {
+    #[tag = 0]
     Percentage,
+    #[tag = 1]
     MaxLeftTop,
+    #[tag = 2]
     MaxRightBottom,
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Argument)]
 pub enum SaveGrid {
-    Left, Right
+    #[tag = 0]
+    Left,
+    #[tag = 1]
+    Right,
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+/// The axis along which a section is split, and the position of the split. Horizontal splits put
+/// one section above the other; vertical splits put them side by side.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Argument)]
 pub enum SplitKind {
-    Horizontal(u32),
-    Vertical(u32),
+    #[tag = 0]
+    Horizontal(#[name = "position"] u32),
+    #[tag = 1]
+    Vertical(#[name = "position"] u32),
 }
 
-impl Argument for ResizeRule {
-    fn from_nums<T>(mut args: T, default: Option<ResizeRule>) -> Option<ResizeRule>
-    where T: Iterator<Item=u64> {
-        args.next().and_then(|arg| match arg {
-            0 => Some(ResizeRule::Percentage),
-            1 => Some(ResizeRule::MaxLeftTop),
-            2 => Some(ResizeRule::MaxRightBottom),
-            _ => None
-        }).or(default)
-    }
-
-    fn encode(&self) -> String {
-        match *self {
-            ResizeRule::Percentage      => String::from("0"),
-            ResizeRule::MaxLeftTop      => String::from("1"),
-            ResizeRule::MaxRightBottom  => String::from("2"),
-        }
-    }
-}
-
-impl Argument for SaveGrid {
-    fn from_nums<T>(mut args: T, default: Option<SaveGrid>) -> Option<SaveGrid>
-    where T: Iterator<Item=u64> {
-        args.next().and_then(|arg| match arg {
-            0 => Some(SaveGrid::Left),
-            1 => Some(SaveGrid::Right),
-            _ => None
-        }).or(default)
-    }
-
-    fn encode(&self) -> String {
-        match *self {
-            SaveGrid::Left  => String::from("0"),
-            SaveGrid::Right => String::from("1"),
-        }
-    }
-}
-
-impl Argument for SplitKind {
-    fn from_nums<T>(mut args: T, default: Option<SplitKind>) -> Option<SplitKind>
-    where T: Iterator<Item=u64> {
-        match (args.next(), args.next()) {
-            (Some(0), Some(n))  => Some(SplitKind::Horizontal(n as u32)),
-            (Some(1), Some(n))  => Some(SplitKind::Vertical(n as u32)),
-            (None, None)        => default,
-            _                   => None,
-        }
-    }
-
-    fn encode(&self) -> String {
-        match *self {
-            SplitKind::Horizontal(n)    => format!("0.{:x}", n),
-            SplitKind::Vertical(n)      => format!("1.{:x}", n),
-        }
-    }
-}
@@ -1,7 +1,9 @@
 mod area;
 mod color;
+mod compound;
 mod coords;
 mod direction;
+mod fields;
 mod grid;
 mod media;
 mod movement;
@@ -9,24 +11,88 @@ mod region;
 mod settings;
 mod style;
 
-pub use self::area::Area;
+pub use self::area::{Area, Cells, CellsIter};
 pub use self::color::Color;
 pub use self::coords::Coords;
 pub use self::direction::Direction;
+pub use self::fields::Fields;
 pub use self::grid::{ResizeRule, SaveGrid, SplitKind};
 pub use self::media::{MediaAlignment, MediaPosition};
 pub use self::movement::Movement;
 pub use self::region::Region;
 pub use self::settings::{BufferSettings, EchoSettings, InputSettings};
-pub use self::style::{Style, ConfigStyle, CodeGroup, DEFAULT_CONFIG_STYLE};
+pub use self::style::{Style, ConfigStyle, CodeGroup, TextStyle, UnderlineShape,
+                      DEFAULT_CONFIG_STYLE};
+
+pub use notty_encoding_derive::Argument;
+
+use std::io;
+
+use encode::Counter;
+use error::{DecodeError, ErrorKind};
+
+pub trait Argument: Clone + Eq {
+    fn from_nums<T>(args: T, default: Option<Self>) -> Option<Self> where T: Iterator<Item=u64>;
+
+    /// Write the encoded form of this argument to a writer, without allocating.
+    fn encode_to<W>(&self, w: &mut W) -> io::Result<()> where W: io::Write + ?Sized;
+
+    fn encode(&self) -> String {
+        let mut buf = Vec::with_capacity(self.encoded_len());
+        let _ = self.encode_to(&mut buf);
+        String::from_utf8(buf).unwrap_or_default()
+    }
+
+    /// The length in bytes of the encoded form of this argument.
+    fn encoded_len(&self) -> usize {
+        let mut counter = Counter::new();
+        let _ = self.encode_to(&mut counter);
+        counter.count()
+    }
 
-pub trait Argument: Copy + Eq {
-    fn from_nums<T>(T, Option<Self>) -> Option<Self> where T: Iterator<Item=u64>;
-    fn encode(&self) -> String;
     fn decode(args: Option<&str>, default: Option<Self>) -> Option<Self> {
         let iter = args.iter().flat_map(|s| s.split('.')).flat_map(|s| u64::from_str_radix(s, 16));
         Self::from_nums(iter, default)
     }
+
+    /// Read this argument from the fields of an encoded argument, returning an error describing
+    /// why if it cannot be read.
+    fn read(fields: &mut Fields) -> Result<Self, DecodeError>;
+
+    /// Read this argument as a field with the given name inside of another argument. Integers
+    /// use the name to describe values which are too large.
+    fn read_named(fields: &mut Fields, _name: &'static str) -> Result<Self, DecodeError> {
+        Self::read(fields)
+    }
+
+    /// Read this argument, or return the default if there are no fields left to read.
+    fn read_or(fields: &mut Fields, default: Self) -> Result<Self, DecodeError> {
+        if fields.is_empty() { Ok(default) } else { Self::read(fields) }
+    }
+
+    /// Read this argument from all of the given fields, rejecting any fields left over. If there
+    /// are no fields, the default is returned if there is one.
+    fn read_fields(mut fields: Fields, default: Option<Self>) -> Result<Self, DecodeError> {
+        let arg = match default {
+            Some(default)   => Self::read_or(&mut fields, default)?,
+            None            => Self::read(&mut fields)?,
+        };
+        fields.finish()?;
+        Ok(arg)
+    }
+
+    /// Decode an argument, returning an error describing why if it cannot be decoded. Unlike
+    /// `decode`, fields which are not hexadecimal, values which are out of range and trailing
+    /// fields are rejected, rather than being ignored or replaced by the default.
+    fn try_decode(args: Option<&str>, default: Option<Self>) -> Result<Self, DecodeError> {
+        Self::read_fields(Fields::new(args.unwrap_or("")), default)
+    }
+
+    /// Decode an argument like `try_decode`, but also reject any value which is too large for
+    /// the field it is decoded into, rather than truncating it.
+    fn decode_strict(args: Option<&str>, default: Option<Self>) -> Result<Self, DecodeError> {
+        Self::read_fields(Fields::strict(args.unwrap_or("")), default)
+    }
 }
 
 impl Argument for bool {
@@ -39,8 +105,57 @@ impl Argument for bool {
         })
     }
 
-    fn encode(&self) -> String {
-        if *self { String::from("1") } else { String::from("0") }
+    fn encode_to<W>(&self, w: &mut W) -> io::Result<()>
+    where W: io::Write + ?Sized {
+        w.write_all(if *self { b"1" } else { b"0" })
+    }
+
+    fn read(fields: &mut Fields) -> Result<bool, DecodeError> {
+        match fields.require()? {
+            0   => Ok(false),
+            1   => Ok(true),
+            _   => Err(fields.error(ErrorKind::OutOfRange)),
+        }
+    }
+}
+
+impl Argument for u8 {
+    fn from_nums<T>(mut args: T, default: Option<u8>) -> Option<u8>
+    where T: Iterator<Item=u64> {
+        args.next().map(|n| n as u8).or(default)
+    }
+
+    fn encode_to<W>(&self, w: &mut W) -> io::Result<()>
+    where W: io::Write + ?Sized {
+        write!(w, "{:x}", self)
+    }
+
+    fn read(fields: &mut Fields) -> Result<u8, DecodeError> {
+        fields.read_u8("value")
+    }
+
+    fn read_named(fields: &mut Fields, name: &'static str) -> Result<u8, DecodeError> {
+        fields.read_u8(name)
+    }
+}
+
+impl Argument for u16 {
+    fn from_nums<T>(mut args: T, default: Option<u16>) -> Option<u16>
+    where T: Iterator<Item=u64> {
+        args.next().map(|n| n as u16).or(default)
+    }
+
+    fn encode_to<W>(&self, w: &mut W) -> io::Result<()>
+    where W: io::Write + ?Sized {
+        write!(w, "{:x}", self)
+    }
+
+    fn read(fields: &mut Fields) -> Result<u16, DecodeError> {
+        fields.read_u16("value")
+    }
+
+    fn read_named(fields: &mut Fields, name: &'static str) -> Result<u16, DecodeError> {
+        fields.read_u16(name)
     }
 }
 
@@ -50,8 +165,17 @@ impl Argument for u32 {
         args.next().map(|n| n as u32).or(default)
     }
 
-    fn encode(&self) -> String {
-        format!("{:x}", self)
+    fn encode_to<W>(&self, w: &mut W) -> io::Result<()>
+    where W: io::Write + ?Sized {
+        write!(w, "{:x}", self)
+    }
+
+    fn read(fields: &mut Fields) -> Result<u32, DecodeError> {
+        fields.read_u32("value")
+    }
+
+    fn read_named(fields: &mut Fields, name: &'static str) -> Result<u32, DecodeError> {
+        fields.read_u32(name)
     }
 }
 
@@ -61,11 +185,53 @@ impl Argument for u64 {
         args.next().or(default)
     }
 
-    fn encode(&self) -> String {
-        format!("{:x}", self)
+    fn encode_to<W>(&self, w: &mut W) -> io::Result<()>
+    where W: io::Write + ?Sized {
+        write!(w, "{:x}", self)
+    }
+
+    fn read(fields: &mut Fields) -> Result<u64, DecodeError> {
+        fields.require()
     }
 }
 
+// Signed integers are zigzag encoded, so that values of small magnitude have short encodings
+// whatever their sign: 0, -1, 1, -2, 2 are encoded as 0, 1, 2, 3, 4.
+fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+fn unzigzag(n: u64) -> i64 {
+    ((n >> 1) as i64) ^ -((n & 1) as i64)
+}
+
+macro_rules! signed {
+    ($($t:ident),*) => {$(
+        impl Argument for $t {
+            fn from_nums<T>(mut args: T, default: Option<$t>) -> Option<$t>
+            where T: Iterator<Item=u64> {
+                args.next().map(|n| unzigzag(n) as $t).or(default)
+            }
+
+            fn encode_to<W>(&self, w: &mut W) -> io::Result<()>
+            where W: io::Write + ?Sized {
+                write!(w, "{:x}", zigzag(*self as i64))
+            }
+
+            fn read(fields: &mut Fields) -> Result<$t, DecodeError> {
+                Self::read_named(fields, "value")
+            }
+
+            fn read_named(fields: &mut Fields, name: &'static str) -> Result<$t, DecodeError> {
+                let n = unzigzag(fields.require()?);
+                fields.narrow_signed(n, $t::MIN as i64, $t::MAX as i64, name).map(|n| n as $t)
+            }
+        }
+    )*}
+}
+
+signed!(i8, i16, i32, i64);
+
 #[cfg(test)]
 mod tests {
 
@@ -77,6 +243,7 @@ mod tests {
     use args::MediaAlignment::*;
     use args::MediaPosition::*;
     use args::Style::*;
+    use error::{DecodeError, ErrorKind};
 
     static AREA_TESTS: &'static [(Area, &'static str)] = &[
         (CursorCell, "1"),
@@ -85,7 +252,7 @@ mod tests {
         (CursorTo(To(Up, 2, false)), "4.2.1.2.0"),
         (CursorBound(Coords { x: 0, y: 0 }), "5.0.0"),
         (WholeScreen, "6"),
-        (Bound(Region { left: 0, top: 0, right: 0x100, bottom: 0x100 }), "6.0.0.100.100"),
+        (Bound(Region { left: 0, top: 0, right: 0x100, bottom: 0x100 }), "a.0.0.100.100"),
         (Rows(0xff, 0xfff), "7.ff.fff"),
         (Columns(0, 0x10), "8.0.10"),
         (BelowCursor(true), "9.1"),
@@ -129,12 +296,18 @@ mod tests {
         (FgColor(Color::Palette(7)), "8.1.7"),
         (BgColor(Color::Default), "9.0"),
         (BgColor(Color::Palette(0xf)), "9.1.f"),
+        (UnderlineShape(super::UnderlineShape::Curly), "b.2"),
+        (UnderlineShape(super::UnderlineShape::Dashed), "b.4"),
+        (UnderlineColor(Color::Palette(0x7c)), "c.1.7c"),
+        (UnderlineColor(Color::Default), "c.0"),
     ];
 
     fn run_test<T: Argument + ::std::fmt::Debug>(strings: &str, args: &[T]) {
-        for (s, &arg) in strings.split(";").zip(args) {
-            assert_eq!(T::decode(Some(s), None), Some(arg));
+        for (s, arg) in strings.split(";").zip(args) {
+            assert_eq!(T::decode(Some(s), None).as_ref(), Some(arg));
+            assert_eq!(T::try_decode(Some(s), None).as_ref(), Ok(arg));
             assert_eq!(arg.encode(), s);
+            assert_eq!(arg.encoded_len(), s.len());
         }
     }
 
@@ -142,8 +315,17 @@ mod tests {
     fn area_argument() {
         for &(area, arg) in AREA_TESTS {
             assert_eq!(Area::decode(Some(arg), None), Some(area));
+            assert_eq!(Area::try_decode(Some(arg), None), Ok(area));
             assert_eq!(area.encode(), arg);
+            assert_eq!(area.encoded_len(), arg.len());
         }
+        // Regions encoded with the tag `Bound` used to share with `WholeScreen`.
+        let bound = Bound(Region { left: 0, top: 0, right: 0x100, bottom: 0x100 });
+        assert_eq!(Area::decode(Some("6.0.0.100.100"), None), Some(bound));
+        assert_eq!(Area::try_decode(Some("6.0.0.100.100"), None), Ok(bound));
+        assert_eq!(Area::decode(Some("6.0.0.0.0"), Some(CursorRow)), Some(CursorRow));
+        assert_eq!(Area::try_decode(Some("6.0.0.0.0"), None),
+                   Err(error(ErrorKind::OutOfRange, Some("0"))));
     }
 
     #[test]
@@ -166,9 +348,51 @@ mod tests {
         run_test("1;2;3;4", &[Up, Down, Left, Right]);
     }
 
+    #[test]
+    fn integer_arguments() {
+        run_test("0;ff", &[0u8, 0xff]);
+        run_test("0;ffff", &[0u16, 0xffff]);
+        run_test("0;1;2;3;4;ff", &[0i8, -1, 1, -2, 2, -128]);
+        run_test("ffffffffffffffff;fffffffffffffffe", &[i64::MIN, i64::MAX]);
+        assert_eq!(i8::decode_strict(Some("100"), None),
+                   Err(error(ErrorKind::Overflow, Some("100")).in_field("value")));
+        assert_eq!(i16::try_decode(Some("10000"), None), Ok(i16::MAX));
+        assert_eq!(i16::try_decode(Some("10001"), None), Ok(i16::MIN));
+        assert_eq!(i8::try_decode(Some("fffffffffffffffe"), None), Ok(i8::MAX));
+    }
+
+    #[test]
+    fn compound_arguments() {
+        run_test("0;1.2;1.4", &[None, Some(Down), Some(Right)]);
+        run_test("2.1.1;1.1.0;1.0", &[(Down, Some(true)), (Up, Some(false)), (Up, None)]);
+        run_test("1.2.3;1.1.1", &[[Up, Down, Left], [Up, Up, Up]]);
+        run_test("0;3.1.2.3", &[vec![], vec![1u8, 2, 3]]);
+        run_test("2.1.1.1.4.0.0",
+                 &[vec![(Color::Palette(1), Some(Right)), (Color::Default, None)]]);
+        assert_eq!(Option::<u8>::try_decode(Some("2"), None),
+                   Err(error(ErrorKind::OutOfRange, Some("2"))));
+        assert_eq!(Vec::<u8>::try_decode(Some("ffffffff.1"), None),
+                   Err(error(ErrorKind::Missing, None)));
+        assert_eq!(Vec::<u8>::decode(Some("ffffffff.1"), None), None);
+        assert_eq!(<[u32; 2]>::try_decode(Some("1.2.3"), None),
+                   Err(error(ErrorKind::TrailingFields, Some("3"))));
+        assert_eq!(<(u8, Coords)>::decode_strict(Some("1.2.100000000"), None),
+                   Err(error(ErrorKind::Overflow, Some("100000000")).in_field("y")));
+    }
+
     #[test]
     fn input_settings_argument() {
-        run_test("1;2", &[Ansi(false), Notty(())]);
+        run_test("1.0;1.1;2;5.1",
+                 &[Ansi(false), Ansi(true), Notty(()), BracketedPasteMode(true)]);
+        run_test("3.8.17.15.3.1c.1a.a.0.4;4.8.17.15", &[
+            LineBufferEcho(EchoSettings { lerase: 8, lnext: 0x17, werase: 0x15 },
+                           BufferSettings { intr: 3, quit: 0x1c, susp: 0x1a, eol1: 0xa, eol2: 0,
+                                            eof: 4 }),
+            ScreenEcho(EchoSettings { lerase: 8, lnext: 0x17, werase: 0x15 }),
+        ]);
+        assert_eq!(InputSettings::try_decode(Some("1"), None), Ok(Ansi(false)));
+        assert_eq!(InputSettings::try_decode(Some("5"), None),
+                   Err(error(ErrorKind::Missing, None)));
     }
 
     #[test]
@@ -186,21 +410,126 @@ mod tests {
     fn movement_argument() {
         for &(movement, arg) in MOVEMENT_TESTS {
             assert_eq!(Movement::decode(Some(arg), None), Some(movement));
+            assert_eq!(Movement::try_decode(Some(arg), None), Ok(movement));
             assert_eq!(movement.encode(), arg);
         }
+        assert_eq!(Movement::try_decode(Some("6"), None), Ok(NextLine(1)));
+        assert_eq!(Movement::try_decode(Some("6.2.0"), None), Ok(NextLine(2)));
+        assert_eq!(Movement::try_decode(Some("9.2"), None),
+                   Err(error(ErrorKind::OutOfRange, Some("2"))));
     }
 
     #[test]
     fn region_argument() {
         run_test("0.1.2.3", &[Region::new(0,1,2,3)]);
+        assert_eq!(Region::try_decode(Some("2.1.2.3"), None),
+                   Err(error(ErrorKind::OutOfRange, Some("3"))));
+        assert_eq!(Region::decode(Some("2.1.2.3"), Some(Region::new(0, 0, 1, 1))),
+                   Some(Region::new(0, 0, 1, 1)));
     }
 
     #[test]
     fn style_argument() {
         for &(style, arg) in STYLE_TESTS {
             assert_eq!(Style::decode(Some(arg), None), Some(style));
+            assert_eq!(Style::try_decode(Some(arg), None), Ok(style));
             assert_eq!(&style.encode(), arg);
         }
     }
 
+    fn error(kind: ErrorKind, token: Option<&str>) -> DecodeError {
+        DecodeError { token: token.map(String::from), ..DecodeError::new(kind) }
+    }
+
+    #[test]
+    fn argument_errors() {
+        assert_eq!(Style::try_decode(Some("1.3"), None),
+                   Err(error(ErrorKind::OutOfRange, Some("3"))));
+        assert_eq!(Style::try_decode(Some("f.1"), None),
+                   Err(error(ErrorKind::UnknownTag, Some("f"))));
+        assert_eq!(Style::try_decode(Some("b.5"), None),
+                   Err(error(ErrorKind::UnknownTag, Some("5"))));
+        assert_eq!(Color::try_decode(Some("1"), None), Err(error(ErrorKind::Missing, None)));
+        assert_eq!(Coords::try_decode(Some("1.zz"), None),
+                   Err(error(ErrorKind::InvalidDigit, Some("zz"))));
+        assert_eq!(Direction::try_decode(Some("1.2"), None),
+                   Err(error(ErrorKind::TrailingFields, Some("2"))));
+        assert_eq!(bool::try_decode(Some("2"), Some(true)),
+                   Err(error(ErrorKind::OutOfRange, Some("2"))));
+        assert_eq!(u32::try_decode(None, None), Err(error(ErrorKind::Missing, None)));
+    }
+
+    #[test]
+    fn strict_decoding_rejects_overflow() {
+        let overflow = |token: &str, field| {
+            DecodeError { field: Some(field), ..error(ErrorKind::Overflow, Some(token)) }
+        };
+        assert_eq!(Style::decode_strict(Some("8.1.1ff"), None), Err(overflow("1ff", "palette")));
+        assert_eq!(Style::try_decode(Some("8.1.1ff"), None), Ok(FgColor(Color::Palette(0xff))));
+        assert_eq!(Style::decode_strict(Some("7.100"), None), Err(overflow("100", "opacity")));
+        assert_eq!(Coords::decode_strict(Some("1.100000000"), None),
+                   Err(overflow("100000000", "y")));
+        assert_eq!(Coords::decode_strict(Some("ffffffff.0"), None),
+                   Ok(Coords { x: 0xffffffff, y: 0 }));
+        assert_eq!(BufferSettings::decode_strict(Some("3.1c.1a.a.0.f00"), None),
+                   Err(overflow("f00", "eof")));
+        assert_eq!(u64::try_decode(Some("10000000000000000"), None),
+                   Err(error(ErrorKind::Overflow, Some("10000000000000000"))));
+    }
+
+    #[test]
+    fn lenient_decoding_fills_defaults() {
+        assert_eq!(bool::decode(Some("2"), Some(true)), Some(true));
+        assert_eq!(bool::try_decode(None, Some(true)), Ok(true));
+        assert_eq!(Coords::decode(Some("1.zz.2"), None), Some(Coords { x: 1, y: 2 }));
+    }
+
+    #[derive(Copy, Clone, Debug, Eq, PartialEq, Argument)]
+    enum Derived {
+        #[tag = 1]
+        Pair(#[name = "first"] u8, #[default = 5] #[max = 9] u32),
+        #[tag = 2]
+        #[flag = false]
+        Off(#[default = 1] u8),
+        #[tag = 2]
+        #[flag = true]
+        On(#[default = 1] u8),
+        #[tag = 0xb]
+        Named { x: u32, #[default = "Direction::Up"] dir: Direction },
+        #[tag = 0xc]
+        Unit,
+    }
+
+    #[test]
+    fn derived_argument() {
+        let tests = [
+            (Derived::Unit, "c"),
+            (Derived::Pair(1, 2), "1.1.2"),
+            (Derived::Off(3), "2.3"),
+            (Derived::On(3), "2.3.1"),
+            (Derived::Named { x: 0x10, dir: Down }, "b.10.2"),
+        ];
+        for &(arg, s) in &tests {
+            assert_eq!(Derived::decode(Some(s), None), Some(arg));
+            assert_eq!(Derived::try_decode(Some(s), None), Ok(arg));
+            assert_eq!(arg.encode(), s);
+            assert_eq!(arg.encoded_len(), s.len());
+        }
+        assert_eq!(Derived::decode(Some("1.1"), None), Some(Derived::Pair(1, 5)));
+        assert_eq!(Derived::try_decode(Some("b.3"), None), Ok(Derived::Named { x: 3, dir: Up }));
+        assert_eq!(Derived::decode(Some("1.1.a"), None), Some(Derived::Pair(1, 5)));
+        assert_eq!(Derived::try_decode(Some("1.1.a"), None),
+                   Err(error(ErrorKind::OutOfRange, Some("a"))));
+        assert_eq!(Derived::decode_strict(Some("1.100"), None),
+                   Err(error(ErrorKind::Overflow, Some("100")).in_field("first")));
+        assert_eq!(Derived::try_decode(Some("2"), None), Ok(Derived::Off(1)));
+        // An invalid flag is an error, but lenient decoding falls back to the false variant.
+        assert_eq!(Derived::try_decode(Some("2.3.2"), None),
+                   Err(error(ErrorKind::OutOfRange, Some("2"))));
+        assert_eq!(Derived::decode(Some("2.3.2"), None), Some(Derived::Off(3)));
+        assert_eq!(Derived::try_decode(Some("d"), None),
+                   Err(error(ErrorKind::UnknownTag, Some("d"))));
+        assert_eq!(Derived::decode(Some("b"), Some(Derived::Unit)), Some(Derived::Unit));
+    }
+
 }
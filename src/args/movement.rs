@@ -1,27 +1,65 @@
-use std::cmp::Ordering;
+use std::cmp::{self, Ordering};
+use std::collections::BTreeSet;
 
-use super::{Argument, Coords, Direction};
+use super::{Coords, Direction, Region};
 use super::Direction::*;
 
 use self::Movement::*;
 
 /// Represents a manner in which the cursor can be moved.
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+///
+/// Movements never take the cursor out of the bounds of the grid; a movement which would leave
+/// them goes as far as it can and stops at the edge. Only `IndexTo` and the line movements scroll
+/// the grid when they go past its edge.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Argument)]
 pub enum Movement {
-    Position(Coords),
-    To(Direction, u32, bool),
-    ToEdge(Direction),
-    IndexTo(Direction, u32),
+    /// Move to these coordinates.
+    #[tag = 1]
+    Position(#[default = "Coords { x: 0, y: 0 }"] Coords),
+    /// Move a number of cells in a direction. If the boolean is true, horizontal movements wrap
+    /// onto the previous or next row when they reach the edge of the grid, stopping at its top
+    /// left or bottom right corner. Vertical movements do not wrap.
+    #[tag = 2]
+    To(#[default = "Direction::Right"] Direction, #[default = 1] u32, #[default = false] bool),
+    /// Move to the edge of the grid in a direction.
+    #[tag = 3]
+    ToEdge(#[default = "Direction::Right"] Direction),
+    /// Move a number of cells in a direction. Moving up past the top of the grid or down past its
+    /// bottom scrolls the grid by the number of rows left over; moving left or right stops at
+    /// the edge of the grid.
+    #[tag = 4]
+    IndexTo(#[default = "Direction::Right"] Direction, #[default = 1] u32),
     /// Arguments:
     /// * Direction of the tab character.
     /// * Number of tab stops "long" the movement should be.
     /// * Whether or not the movement should wrap when it reaches the end of the screen.
-    Tab(Direction, u32, bool ),
-    Column(u32),
-    Row(u32),
-    PreviousLine(u32),
-    NextLine(u32),
+    ///
+    /// The edges of the grid act as tab stops. A wrapping tab which starts at the edge moves to
+    /// the opposite edge of the previous or next row, counting as one tab stop. Vertical tabs
+    /// move by cells, like `To`.
+    #[tag = 5]
+    Tab(#[default = "Direction::Right"] Direction, #[default = 1] u32, #[default = false] bool),
+    /// Move to this column, within the row of the cursor.
+    #[tag = 7]
+    Column(#[default = 0] u32),
+    /// Move to this row, within the column of the cursor.
+    #[tag = 8]
+    Row(#[default = 0] u32),
+    /// Move up a number of rows to the first column, scrolling like `IndexTo`.
+    #[tag = 6]
+    #[flag = true]
+    PreviousLine(#[default = 1] u32),
+    /// Move down a number of rows to the first column, scrolling like `IndexTo`.
+    #[tag = 6]
+    #[flag = false]
+    NextLine(#[default = 1] u32),
+    /// Move to the top left corner of the grid.
+    #[tag = 9]
+    #[flag = true]
     ToBeginning,
+    /// Move to the bottom right corner of the grid.
+    #[tag = 9]
+    #[flag = false]
     ToEnd,
 }
 
@@ -51,78 +89,229 @@ impl Movement {
 
     /// Returns true if this motion can cause the screen to scroll.
     pub fn scrolls(&self) -> bool {
-        match *self {
-            IndexTo(..) | PreviousLine(_) | NextLine(_) => true,
-           _                                            => false,
-        }
+        matches!(*self, IndexTo(..) | PreviousLine(_) | NextLine(_))
     }
-}
 
-impl Argument for Movement {
-    fn from_nums<T>(mut args: T, default: Option<Movement>) -> Option<Movement>
-    where T: Iterator<Item=u64> {
-        match args.next() {
-            // Position
-            Some(0x1)   => Coords::from_nums(args, Some(Coords {x: 0, y: 0})).map(Position),
-            // To
-            Some(0x2)   => {
-                let dir = Direction::from_nums(args.by_ref(), Some(Right)).unwrap();
-                let n = u32::from_nums(args.by_ref(), Some(1)).unwrap();
-                let wrap = bool::from_nums(args, Some(false)).unwrap();
-                Some(To(dir, n, wrap))
+    /// Returns where the cursor ends up on taking this movement within the bounds, and the number
+    /// of rows the contents of the bounds scroll because the movement went past their top or
+    /// bottom edge.
+    ///
+    /// The tab stops are columns in the same coordinates as the cursor; those outside of the
+    /// bounds are ignored. A cursor outside of the bounds is moved to the nearest coordinates
+    /// within them first. If the bounds are empty, the cursor does not move.
+    pub fn apply(&self, cursor: Coords, bounds: Region, tab_stops: &BTreeSet<u32>)
+            -> (Coords, u32) {
+        if bounds.left >= bounds.right || bounds.top >= bounds.bottom { return (cursor, 0) }
+        let Coords { x, y } = bounds.xy_within(cursor);
+        let (last_x, last_y) = (bounds.right - 1, bounds.bottom - 1);
+        let at = |x, y| Coords { x: x, y: y };
+        match *self {
+            Position(coords)                            => (bounds.xy_within(coords), 0),
+            To(Up, n, _) | Tab(Up, n, _)                => {
+                (at(x, cmp::max(y.saturating_sub(n), bounds.top)), 0)
+            }
+            To(Down, n, _) | Tab(Down, n, _)            => {
+                (at(x, cmp::min(y.saturating_add(n), last_y)), 0)
             }
-            // ToEdge
-            Some(0x3)   => Direction::from_nums(args, Some(Right)).map(ToEdge),
-            // IndexTo
-            Some(0x4)   => {
-                let dir = Direction::from_nums(args.by_ref(), Some(Right)).unwrap();
-                let n = u32::from_nums(args.by_ref(), Some(1)).unwrap();
-                Some(IndexTo(dir, n))
+            To(Left, n, false) | IndexTo(Left, n)       => {
+                (at(cmp::max(x.saturating_sub(n), bounds.left), y), 0)
             }
-            // Tab
-            Some(0x5)   => {
-                let dir = Direction::from_nums(args.by_ref(), Some(Right)).unwrap();
-                let n = u32::from_nums(args.by_ref(), Some(1)).unwrap();
-                let wrap = bool::from_nums(args, Some(false)).unwrap();
-                Some(Tab(dir, n, wrap))
+            To(Right, n, false) | IndexTo(Right, n)     => {
+                (at(cmp::min(x.saturating_add(n), last_x), y), 0)
             }
-            // PreviousLine/NextLine
-            Some(0x6)   => {
-                let n = u32::from_nums(args.by_ref(), Some(1)).unwrap();
-                match bool::from_nums(args, Some(false)).unwrap() {
-                    true    => Some(PreviousLine(n)),
-                    false   => Some(NextLine(n)),
+            // Wrapping movements continue onto the previous or next row, stopping at the corners.
+            To(dir, n, true)                            => {
+                let width = bounds.width() as u64;
+                let pos = (y - bounds.top) as u64 * width + (x - bounds.left) as u64;
+                let pos = match dir {
+                    Left    => pos.saturating_sub(n as u64),
+                    _       => cmp::min(pos + n as u64, bounds.height() as u64 * width - 1),
+                };
+                (at(bounds.left + (pos % width) as u32, bounds.top + (pos / width) as u32), 0)
+            }
+            ToEdge(Up)                                  => (at(x, bounds.top), 0),
+            ToEdge(Down)                                => (at(x, last_y), 0),
+            ToEdge(Left)                                => (at(bounds.left, y), 0),
+            ToEdge(Right)                               => (at(last_x, y), 0),
+            IndexTo(Up, n)                              => {
+                match y.checked_sub(n).filter(|&to| to >= bounds.top) {
+                    Some(to)    => (at(x, to), 0),
+                    None        => (at(x, bounds.top), n - (y - bounds.top)),
                 }
             }
-            // Column
-            Some(0x7)   => u32::from_nums(args, Some(0)).map(Column),
-            // Row
-            Some(0x8)   => u32::from_nums(args, Some(0)).map(Row),
-            // ToBeginning/ToEnd
-            Some(0x9)   => {
-                match bool::from_nums(args, Some(false)).unwrap() {
-                    true    => Some(ToBeginning),
-                    false   => Some(ToEnd),
+            IndexTo(Down, n)                            => {
+                match y.checked_add(n).filter(|&to| to <= last_y) {
+                    Some(to)    => (at(x, to), 0),
+                    None        => (at(x, last_y), n - (last_y - y)),
                 }
             }
-            _                   => default,
+            PreviousLine(n)                             => {
+                let (coords, scrolled) = IndexTo(Up, n).apply(cursor, bounds, tab_stops);
+                (at(bounds.left, coords.y), scrolled)
+            }
+            NextLine(n)                                 => {
+                let (coords, scrolled) = IndexTo(Down, n).apply(cursor, bounds, tab_stops);
+                (at(bounds.left, coords.y), scrolled)
+            }
+            Tab(dir, n, wrap)                           => (tab(dir, n, wrap, at(x, y), bounds,
+                                                                tab_stops), 0),
+            Column(n)                                   => (at(bounds.x_within(n), y), 0),
+            Row(n)                                      => (at(x, bounds.y_within(n)), 0),
+            ToBeginning                                 => (at(bounds.left, bounds.top), 0),
+            ToEnd                                       => (at(last_x, last_y), 0),
         }
     }
+}
 
-    fn encode(&self) -> String {
-        match *self {
-            Position(coords)    => format!("1.{}", coords.encode()),
-            To(dir, n, wrap)    => format!("2.{}.{:x}.{}", dir.encode(), n, wrap.encode()),
-            ToEdge(dir)         => format!("3.{}", dir.encode()),
-            IndexTo(dir, n)     => format!("4.{}.{:x}", dir.encode(), n),
-            Tab(dir, n, wrap)   => format!("5.{}.{:x}.{}", dir.encode(), n, wrap.encode()),
-            PreviousLine(n)     => format!("6.{:x}.1", n),
-            NextLine(n)         => format!("6.{:x}", n),
-            Column(n)           => format!("7.{:x}", n),
-            Row(n)              => format!("8.{:x}", n),
-            ToBeginning         => String::from("9.1"),
-            ToEnd               => String::from("9"),
+// Move across a number of tab stops. The edges of the bounds act as tab stops; wrapping tabs
+// continue from the opposite edge of the next or previous row.
+fn tab(dir: Direction, n: u32, wrap: bool, mut cursor: Coords, bounds: Region,
+       tab_stops: &BTreeSet<u32>) -> Coords {
+    let (last_x, last_y) = (bounds.right - 1, bounds.bottom - 1);
+    for _ in 0..n {
+        let stop = match dir {
+            Left    => tab_stops.range(..cursor.x).next_back().cloned()
+                           .filter(|&x| x > bounds.left)
+                           .or(if cursor.x > bounds.left { Some(bounds.left) } else { None }),
+            _       => tab_stops.range(cursor.x + 1..).next().cloned()
+                           .filter(|&x| x < bounds.right)
+                           .or(if cursor.x < last_x { Some(last_x) } else { None }),
+        };
+        match stop {
+            Some(x)                                     => cursor.x = x,
+            None if wrap && dir == Left && cursor.y > bounds.top    => {
+                cursor = Coords { x: last_x, y: cursor.y - 1 };
+            }
+            None if wrap && dir != Left && cursor.y < last_y        => {
+                cursor = Coords { x: bounds.left, y: cursor.y + 1 };
+            }
+            None                                        => break,
         }
     }
+    cursor
 }
 
+#[cfg(test)]
+mod tests {
+
+    use std::collections::BTreeSet;
+
+    use args::{Coords, Direction, Region};
+    use args::Direction::*;
+
+    use super::Movement;
+    use super::Movement::*;
+
+    static BOUNDS: Region = Region { left: 0, top: 0, right: 10, bottom: 5 };
+
+    fn apply(movement: Movement, bounds: Region, x: u32, y: u32) -> (u32, u32, u32) {
+        let tab_stops: BTreeSet<u32> = [4, 8, 12].iter().cloned().collect();
+        let (coords, scrolled) = movement.apply(Coords { x: x, y: y }, bounds, &tab_stops);
+        (coords.x, coords.y, scrolled)
+    }
+
+    fn check(movements: &[(Movement, (u32, u32, u32))]) {
+        for &(movement, expected) in movements {
+            assert_eq!(apply(movement, BOUNDS, 5, 2), expected, "{:?}", movement);
+        }
+    }
+
+    #[test]
+    fn positions() {
+        check(&[
+            (Position(Coords { x: 3, y: 4 }), (3, 4, 0)),
+            (Position(Coords { x: 20, y: 1 }), (9, 1, 0)),
+            (Column(3), (3, 2, 0)),
+            (Column(20), (9, 2, 0)),
+            (Row(0), (5, 0, 0)),
+            (Row(20), (5, 4, 0)),
+            (ToBeginning, (0, 0, 0)),
+            (ToEnd, (9, 4, 0)),
+        ]);
+    }
+
+    #[test]
+    fn moving_to_edges() {
+        check(&[
+            (ToEdge(Up), (5, 0, 0)),
+            (ToEdge(Down), (5, 4, 0)),
+            (ToEdge(Left), (0, 2, 0)),
+            (ToEdge(Right), (9, 2, 0)),
+        ]);
+    }
+
+    #[test]
+    fn moving_by_cells() {
+        check(&[
+            (To(Up, 1, false), (5, 1, 0)),
+            (To(Up, 9, false), (5, 0, 0)),
+            (To(Down, 9, true), (5, 4, 0)),
+            (To(Left, 2, false), (3, 2, 0)),
+            (To(Left, 9, false), (0, 2, 0)),
+            (To(Right, 9, false), (9, 2, 0)),
+            (To(Left, 7, true), (8, 1, 0)),
+            (To(Right, 7, true), (2, 3, 0)),
+            (To(Left, 100, true), (0, 0, 0)),
+            (To(Right, u32::MAX, true), (9, 4, 0)),
+        ]);
+    }
+
+    #[test]
+    fn indexing() {
+        check(&[
+            (IndexTo(Up, 2), (5, 0, 0)),
+            (IndexTo(Up, 5), (5, 0, 3)),
+            (IndexTo(Down, 2), (5, 4, 0)),
+            (IndexTo(Down, u32::MAX), (5, 4, u32::MAX - 2)),
+            (IndexTo(Left, 9), (0, 2, 0)),
+            (IndexTo(Right, 2), (7, 2, 0)),
+            (PreviousLine(1), (0, 1, 0)),
+            (PreviousLine(4), (0, 0, 2)),
+            (NextLine(1), (0, 3, 0)),
+            (NextLine(4), (0, 4, 2)),
+        ]);
+    }
+
+    #[test]
+    fn tabs() {
+        check(&[
+            (Tab(Right, 1, false), (8, 2, 0)),
+            (Tab(Right, 2, false), (9, 2, 0)),
+            (Tab(Right, 3, false), (9, 2, 0)),
+            (Tab(Right, 3, true), (0, 3, 0)),
+            (Tab(Right, 4, true), (4, 3, 0)),
+            (Tab(Right, 100, true), (9, 4, 0)),
+            (Tab(Left, 1, false), (4, 2, 0)),
+            (Tab(Left, 2, false), (0, 2, 0)),
+            (Tab(Left, 3, true), (9, 1, 0)),
+            (Tab(Left, 4, true), (8, 1, 0)),
+            (Tab(Left, 100, true), (0, 0, 0)),
+            (Tab(Up, 1, true), (5, 1, 0)),
+            (Tab(Down, 1, false), (5, 3, 0)),
+        ]);
+    }
+
+    #[test]
+    fn movements_within_offset_bounds() {
+        let bounds = Region { left: 2, top: 1, right: 6, bottom: 4 };
+        let movements = [
+            (ToBeginning, 5, 3, (2, 1, 0)),
+            (Column(0), 5, 3, (2, 3, 0)),
+            (Tab(Right, 1, false), 2, 1, (4, 1, 0)),
+            (Tab(Right, 2, false), 2, 1, (5, 1, 0)),
+            (Tab(Right, 2, true), 5, 1, (4, 2, 0)),
+            (Tab(Left, 1, true), 2, 2, (5, 1, 0)),
+            (To(Right, 5, true), 3, 1, (4, 2, 0)),
+            (NextLine(3), 4, 2, (2, 3, 2)),
+            // A cursor outside of the bounds is moved within them first.
+            (To(Left, 1, false), 9, 0, (4, 1, 0)),
+            (IndexTo(Up, 1), 0, 0, (2, 1, 1)),
+        ];
+        for &(movement, x, y, expected) in &movements {
+            assert_eq!(apply(movement, bounds, x, y), expected, "{:?} from {}, {}", movement, x, y);
+        }
+        let empty = Region { left: 3, top: 3, right: 3, bottom: 4 };
+        assert_eq!(apply(ToEnd, empty, 7, 7), (7, 7, 0));
+        assert_eq!(apply(Tab(Direction::Right, 1, true), empty, 3, 3), (3, 3, 0));
+    }
+}
@@ -1,7 +1,7 @@
 use std::cmp;
 use std::ops::Index;
 
-use super::{Argument, Coords, SplitKind, ResizeRule};
+use super::{Coords, SplitKind, ResizeRule};
 use super::SplitKind::*;
 use super::ResizeRule::*;
 
@@ -9,7 +9,8 @@ use super::ResizeRule::*;
 ///
 /// The region is incluse of the top and left boundary and exclusive of the bottom and right
 /// boundary.
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Argument)]
+#[new = "Region::checked_new"]
 pub struct Region {
     pub left: u32,
     pub top: u32,
@@ -19,18 +20,34 @@ pub struct Region {
 
 impl Region {
     /// Creates a region. Note that x1/x2 and y1/y2 need not be properly ordered, but one of them 
-    /// __must__ be greater than the other. This function will panic otherwise.
+    /// __must__ be greater than the other. This function will panic otherwise; `checked_new`
+    /// returns `None` instead.
     pub fn new(x1: u32, y1: u32, x2: u32, y2: u32) -> Region {
+        Region::checked_new(x1, y1, x2, y2).expect("a region cannot be empty")
+    }
+
+    /// Creates a region, if it would not be empty.
+    pub fn checked_new(x1: u32, y1: u32, x2: u32, y2: u32) -> Option<Region> {
         let (left, right) = (cmp::min(x1, x2), cmp::max(x1, x2));
         let (top, bottom) = (cmp::min(y1, y2), cmp::max(y1, y2));
-        assert!(right > left);
-        assert!(bottom > top);
-        Region {
+        if left == right || top == bottom { return None }
+        Some(Region {
             left: left,
             top: top,
             right: right,
             bottom: bottom,
-        }
+        })
+    }
+
+    /// Returns true if this region contains no cells. Regions built by `new` are never empty, but
+    /// those built by `clip`, or directly, can be.
+    pub fn is_empty(&self) -> bool {
+        self.left >= self.right || self.top >= self.bottom
+    }
+
+    /// Returns the number of cells in this region.
+    pub fn area(&self) -> u64 {
+        if self.is_empty() { 0 } else { self.width() as u64 * self.height() as u64 }
     }
 
     /// Returns true if a given coordinates is contained within this region.
@@ -71,12 +88,14 @@ impl Region {
         Coords { x: x.saturating_sub(self.left), y: y.saturating_sub(self.top) }
     }
 
+    /// Move this region as little as possible so that it contains the coordinates, keeping its
+    /// size.
     pub fn move_to_contain(&self, coords: Coords) -> Region {
         match (coords.x < self.left, self.right <= coords.x,
                coords.y < self.top, self.bottom <= coords.y) {
             // Left and above
             (true, false, true, false)  =>
-                Region::new(coords.x, coords.y, coords.x + self.width(), coords.x + self.height()),
+                Region::new(coords.x, coords.y, coords.x + self.width(), coords.y + self.height()),
             // Left and below
             (true, false, false, true)  =>
                 Region::new(coords.x,
@@ -125,22 +144,124 @@ impl Region {
         }
     }
 
+    /// Returns true if this region and another have any cells in common.
+    pub fn intersects(&self, other: Region) -> bool {
+        self.intersection(other).is_some()
+    }
+
+    /// Returns true if every cell of another region is in this region.
+    pub fn contains_region(&self, other: Region) -> bool {
+        other.is_empty() || (self.left <= other.left && other.right <= self.right
+                             && self.top <= other.top && other.bottom <= self.bottom)
+    }
+
+    /// The cells this region and another have in common, if there are any.
+    pub fn intersection(&self, other: Region) -> Option<Region> {
+        let region = Region {
+            left: cmp::max(self.left, other.left),
+            top: cmp::max(self.top, other.top),
+            right: cmp::min(self.right, other.right),
+            bottom: cmp::min(self.bottom, other.bottom),
+        };
+        if region.is_empty() { None } else { Some(region) }
+    }
+
+    /// The smallest region containing both this region and another. Empty regions are ignored.
+    pub fn union(&self, other: Region) -> Region {
+        if other.is_empty() { return *self }
+        if self.is_empty() { return other }
+        Region {
+            left: cmp::min(self.left, other.left),
+            top: cmp::min(self.top, other.top),
+            right: cmp::max(self.right, other.right),
+            bottom: cmp::max(self.bottom, other.bottom),
+        }
+    }
+
+    /// The cells of this region which are not in another, as up to four disjoint regions: the
+    /// rows above the other region, the columns to its left and right, and the rows below it.
+    pub fn subtract(&self, other: Region) -> Vec<Region> {
+        let cut = match self.intersection(other) {
+            Some(cut)   => cut,
+            None        => return if self.is_empty() { vec![] } else { vec![*self] },
+        };
+        let parts = [
+            Region { bottom: cut.top, ..*self },
+            Region { top: cut.top, right: cut.left, bottom: cut.bottom, ..*self },
+            Region { left: cut.right, top: cut.top, bottom: cut.bottom, ..*self },
+            Region { top: cut.bottom, ..*self },
+        ];
+        parts.iter().cloned().filter(|part| !part.is_empty()).collect()
+    }
+
+    /// Move this region by an offset in each direction, if it would still be on the grid.
+    pub fn translate(&self, dx: i64, dy: i64) -> Option<Region> {
+        let shift = |n: u32, d: i64| {
+            let n = n as i64 + d;
+            if 0 <= n && n <= u32::MAX as i64 { Some(n as u32) } else { None }
+        };
+        Some(Region {
+            left: shift(self.left, dx)?,
+            top: shift(self.top, dy)?,
+            right: shift(self.right, dx)?,
+            bottom: shift(self.bottom, dy)?,
+        })
+    }
+
+    /// The part of this region within the bounds. Unlike `intersection`, this always returns a
+    /// region; if this region lies outside of the bounds, the result is an empty region at the
+    /// nearest edge of the bounds.
+    pub fn clip(&self, bounds: Region) -> Region {
+        let clamp = |n: u32, min: u32, max: u32| cmp::max(cmp::min(n, max), min);
+        let left = clamp(self.left, bounds.left, bounds.right);
+        let top = clamp(self.top, bounds.top, bounds.bottom);
+        Region {
+            left: left,
+            top: top,
+            right: clamp(self.right, left, bounds.right),
+            bottom: clamp(self.bottom, top, bounds.bottom),
+        }
+    }
+
+    /// Iterate over the rows of this region, from top to bottom, as regions one cell high.
+    pub fn rows(&self) -> impl Iterator<Item=Region> {
+        let region = *self;
+        (region.top..region.bottom).map(move |y| Region { top: y, bottom: y + 1, ..region })
+    }
+
+    /// Iterate over the columns of this region, from left to right, as regions one cell wide.
+    pub fn columns(&self) -> impl Iterator<Item=Region> {
+        let region = *self;
+        (region.left..region.right).map(move |x| Region { left: x, right: x + 1, ..region })
+    }
+
+    /// Iterate over the coordinates of the cells in this region, in reading order.
+    pub fn cells(&self) -> impl Iterator<Item=Coords> {
+        let Region { left, right, .. } = *self;
+        (self.top..self.bottom).flat_map(move |y| (left..right).map(move |x| Coords { x: x, y: y }))
+    }
+
+    /// Split the region in two along the boundary described by the kind of split, returning the
+    /// kind of split with the absolute position of that boundary, the left or top region and the
+    /// right or bottom region.
+    ///
+    /// The rule determines how the position of the split is read: under `MaxLeftTop` it is the
+    /// width or height of the left or top region, under `MaxRightBottom` it is that of the right
+    /// or bottom region, and under `Percentage` it is the percentage of the region given to the
+    /// left or top region. Splitting the same region again after it has been resized keeps the
+    /// size of the side named by the rule, or the proportions of the split.
+    ///
+    /// Both regions are at least one cell wide and high if the region can be split; a region
+    /// which is only one cell wide or high along the axis of the split gives an empty left or top
+    /// region.
     pub fn split(self, kind: SplitKind, rule: ResizeRule) -> (SplitKind, Region, Region) {
-        match (kind, rule) {
-            (Horizontal(n), MaxLeftTop) | (Horizontal(n), Percentage)   => {
-                let n = cmp::min(self.top + n, self.bottom - 1);
-                (Horizontal(n), Region { bottom: n, ..self }, Region { top: n, ..self })
-            }
-            (Horizontal(n), MaxRightBottom)                             => {
-                let n = cmp::min(n, cmp::max(self.bottom.saturating_sub(n), self.top + 1));
+        match kind {
+            Horizontal(n)   => {
+                let n = boundary(self.top, self.bottom, n, rule);
                 (Horizontal(n), Region { bottom: n, ..self }, Region { top: n, ..self })
             }
-            (Vertical(n), MaxLeftTop) | (Vertical(n), Percentage)       => {
-                let n = cmp::min(self.left + n, self.right - 1);
-                (Vertical(n), Region { right: n, ..self }, Region { left: n, ..self })
-            }
-            (Vertical(n), MaxRightBottom)                               => {
-                let n = cmp::min(n, cmp::max(self.right.saturating_sub(n), self.left + 1));
+            Vertical(n)     => {
+                let n = boundary(self.left, self.right, n, rule);
                 (Vertical(n), Region { right: n, ..self }, Region { left: n, ..self })
             }
         }
@@ -148,7 +269,20 @@ impl Region {
 
 }
 
-// This implementation is so that regions can act as mocks of 2d arrays.
+// The absolute position of a split between start and end, leaving at least one cell on each side.
+fn boundary(start: u32, end: u32, n: u32, rule: ResizeRule) -> u32 {
+    let len = end.saturating_sub(start);
+    if len < 2 { return start }
+    let offset = match rule {
+        MaxLeftTop      => n,
+        MaxRightBottom  => len.saturating_sub(n),
+        Percentage      => ((len as u64 * cmp::min(n, 100) as u64 + 50) / 100) as u32,
+    };
+    start + cmp::max(cmp::min(offset, len - 1), 1)
+}
+
+// This implementation is so that regions can act as mocks of 2d arrays; `screen::Grid` is the
+// real thing.
 impl Index<Coords> for Region {
     type Output = ();
 
@@ -159,21 +293,6 @@ impl Index<Coords> for Region {
     }
 }
 
-impl Argument for Region {
-    fn from_nums<T>(mut args: T, default: Option<Region>) -> Option<Region>
-    where T: Iterator<Item=u64> {
-        match (args.next(), args.next(), args.next(), args.next()) {
-            (Some(l), Some(t), Some(r), Some(b)) => Some(Region::new(l as u32, t as u32,
-                                                                     r as u32, b as u32)),
-            _                                    => default
-        }
-    }
-
-    fn encode(&self) -> String {
-        format!("{:x}.{:x}.{:x}.{:x}", self.left, self.top, self.right, self.bottom)
-    }
-}
-
 
 #[cfg(test)]
 mod tests {
@@ -208,4 +327,133 @@ mod tests {
         }
     }
 
+    #[test]
+    fn region_move_to_contain() {
+        let region = Region::new(10, 10, 20, 15);
+        let moves = [
+            ((5, 2), Region::new(5, 2, 15, 7)),
+            ((5, 20), Region::new(5, 16, 15, 21)),
+            ((25, 2), Region::new(16, 2, 26, 7)),
+            ((25, 20), Region::new(16, 16, 26, 21)),
+            ((5, 12), Region::new(5, 10, 15, 15)),
+            ((25, 12), Region::new(16, 10, 26, 15)),
+            ((12, 2), Region::new(10, 2, 20, 7)),
+            ((12, 20), Region::new(10, 16, 20, 21)),
+            ((12, 12), region),
+            ((19, 14), region),
+        ];
+        for &((x, y), moved) in &moves {
+            let coords = Coords { x: x, y: y };
+            assert_eq!(region.move_to_contain(coords), moved, "{:?}", coords);
+            assert!(moved.contains(coords));
+        }
+    }
+
+    #[test]
+    fn region_checked_new() {
+        assert_eq!(Region::checked_new(5, 1, 2, 3), Some(Region { left: 2, top: 1, right: 5,
+                                                                  bottom: 3 }));
+        assert_eq!(Region::checked_new(5, 1, 5, 3), None);
+        assert_eq!(Region::checked_new(0, 0, 1, 0), None);
+    }
+
+    #[test]
+    fn region_set_operations() {
+        let a = Region::new(0, 0, 6, 4);
+        let b = Region::new(2, 1, 8, 3);
+        let c = Region::new(6, 0, 9, 9);
+        assert_eq!(a.intersection(b), Some(Region::new(2, 1, 6, 3)));
+        assert_eq!(a.intersection(c), None);
+        assert!(a.intersects(b) && !a.intersects(c));
+        assert_eq!(a.union(c), Region::new(0, 0, 9, 9));
+        assert_eq!(a.union(Region { left: 50, top: 50, right: 50, bottom: 60 }), a);
+        assert!(a.contains_region(Region::new(1, 1, 6, 4)));
+        assert!(!a.contains_region(b));
+
+        assert_eq!(a.subtract(b), [
+            Region::new(0, 0, 6, 1),
+            Region::new(0, 1, 2, 3),
+            Region::new(0, 3, 6, 4),
+        ]);
+        assert_eq!(a.subtract(c), [a]);
+        assert_eq!(b.subtract(a), [Region::new(6, 1, 8, 3)]);
+        assert_eq!(b.subtract(Region::new(0, 0, 9, 9)), []);
+        let hole = Region::new(2, 1, 4, 3);
+        let parts = a.subtract(hole);
+        assert_eq!(parts.len(), 4);
+        assert_eq!(parts.iter().map(Region::area).sum::<u64>(), a.area() - hole.area());
+        assert!(parts.iter().all(|part| !part.intersects(hole)));
+    }
+
+    #[test]
+    fn region_translate_and_clip() {
+        let region = Region::new(2, 3, 5, 7);
+        assert_eq!(region.translate(-2, 4), Some(Region::new(0, 7, 3, 11)));
+        assert_eq!(region.translate(-3, 0), None);
+        assert_eq!(region.translate(0, u32::MAX as i64), None);
+
+        let bounds = Region::new(0, 0, 4, 4);
+        assert_eq!(region.clip(bounds), Region::new(2, 3, 4, 4));
+        let outside = Region::new(6, 1, 9, 2).clip(bounds);
+        assert!(outside.is_empty());
+        assert_eq!(outside.area(), 0);
+        assert_eq!((outside.left, outside.top), (4, 1));
+    }
+
+    #[test]
+    fn region_iterators() {
+        let region = Region::new(1, 2, 3, 4);
+        assert_eq!(region.rows().collect::<Vec<_>>(), [Region::new(1, 2, 3, 3),
+                                                       Region::new(1, 3, 3, 4)]);
+        assert_eq!(region.columns().collect::<Vec<_>>(), [Region::new(1, 2, 2, 4),
+                                                          Region::new(2, 2, 3, 4)]);
+        let cells: Vec<_> = region.cells().map(|Coords { x, y }| (x, y)).collect();
+        assert_eq!(cells, [(1, 2), (2, 2), (1, 3), (2, 3)]);
+        assert_eq!(Region { left: 1, top: 1, right: 1, bottom: 4 }.cells().count(), 0);
+    }
+
+    #[test]
+    fn region_split() {
+        let splits = [
+            (Horizontal(30), MaxLeftTop, Horizontal(40)),
+            (Horizontal(30), MaxRightBottom, Horizontal(70)),
+            (Horizontal(30), Percentage, Horizontal(37)),
+            (Horizontal(0), MaxLeftTop, Horizontal(11)),
+            (Horizontal(200), MaxRightBottom, Horizontal(11)),
+            (Horizontal(200), Percentage, Horizontal(99)),
+            (Vertical(25), MaxLeftTop, Vertical(25)),
+            (Vertical(25), MaxRightBottom, Vertical(75)),
+            (Vertical(25), Percentage, Vertical(25)),
+            (Vertical(100), MaxLeftTop, Vertical(99)),
+        ];
+        for &(kind, rule, split) in &splits {
+            let (at, left, right) = REGION.split(kind, rule);
+            assert_eq!(at, split, "{:?} {:?}", kind, rule);
+            match at {
+                Horizontal(n)   => {
+                    assert_eq!(left, Region { bottom: n, ..REGION });
+                    assert_eq!(right, Region { top: n, ..REGION });
+                }
+                Vertical(n)     => {
+                    assert_eq!(left, Region { right: n, ..REGION });
+                    assert_eq!(right, Region { left: n, ..REGION });
+                }
+            }
+        }
+        let thin = Region { left: 3, top: 3, right: 4, bottom: 9 };
+        assert_eq!(thin.split(Vertical(1), MaxLeftTop).0, Vertical(3));
+        assert_eq!(thin.split(Horizontal(50), Percentage).0, Horizontal(6));
+    }
+
+    #[test]
+    fn region_split_after_resizing() {
+        let small = Region::new(0, 0, 20, 10);
+        let large = Region::new(0, 0, 80, 30);
+        assert_eq!(small.split(Vertical(25), Percentage).0, Vertical(5));
+        assert_eq!(large.split(Vertical(25), Percentage).0, Vertical(20));
+        assert_eq!(small.split(Horizontal(4), MaxRightBottom).0, Horizontal(6));
+        assert_eq!(large.split(Horizontal(4), MaxRightBottom).0, Horizontal(26));
+        assert_eq!(large.split(Horizontal(4), MaxLeftTop).0, Horizontal(4));
+    }
+
 }
@@ -1,58 +1,131 @@
-use super::{Argument, Color};
-use self::Style::*;
-use self::CodeGroup::*;
+use super::Color;
 
 /// Set rich text styles. Booleans represent on or off.
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Argument)]
 pub enum Style {
     /// Field is number of underlines (between 0 and 2).
-    Underline(u8),
-    Bold(bool),
-    Italic(bool),
-    Blink(bool),
-    InvertColors(bool),
-    Strikethrough(bool),
-    Opacity(u8),
+    #[tag = 0x1]
+    Underline(#[default = 1] #[max = 2] u8),
+    #[tag = 0x2]
+    Bold(#[default = true] bool),
+    #[tag = 0x3]
+    Italic(#[default = true] bool),
+    #[tag = 0x4]
+    Blink(#[default = true] bool),
+    #[tag = 0x5]
+    InvertColors(#[default = true] bool),
+    #[tag = 0x6]
+    Strikethrough(#[default = true] bool),
+    #[tag = 0x7]
+    Opacity(#[default = 0xff] #[name = "opacity"] u8),
+    #[tag = 0x8]
     FgColor(Color),
+    #[tag = 0x9]
     BgColor(Color),
+    #[tag = 0xa]
     Configured(ConfigStyle),
+    /// The shape of the underlines drawn when the number of underlines is not 0.
+    #[tag = 0xb]
+    UnderlineShape(UnderlineShape),
+    /// The color of underlines. The default color is the color of the text.
+    #[tag = 0xc]
+    UnderlineColor(Color),
+}
+
+/// The shape of an underline.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash, Argument)]
+pub enum UnderlineShape {
+    #[default]
+    #[tag = 1]
+    Straight,
+    #[tag = 2]
+    Curly,
+    #[tag = 3]
+    Dotted,
+    #[tag = 4]
+    Dashed,
+}
+
+/// A complete set of rich text styles, such as the styles of a cell or of the text written from
+/// now on.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TextStyle {
+    /// The number of underlines, between 0 and 2.
+    pub underline: u8,
+    pub underline_shape: UnderlineShape,
+    pub underline_color: Color,
+    pub bold: bool,
+    pub italic: bool,
+    pub blink: bool,
+    pub inverted: bool,
+    pub strikethrough: bool,
+    pub opacity: u8,
+    pub fg: Color,
+    pub bg: Color,
+    pub configured: ConfigStyle,
 }
 
-impl Argument for Style {
-    fn from_nums<T>(mut args: T, default: Option<Style>) -> Option<Style>
-    where T: Iterator<Item=u64> {
-        match args.next() {
-            Some(0x1)   => match args.next() {
-                Some(0)         => Some(Underline(0)),
-                Some(1) | None  => Some(Underline(1)),
-                Some(2)         => Some(Underline(2)),
-                _               => None
-            },
-            Some(0x2)   => bool::from_nums(args, Some(true)).map(Bold),
-            Some(0x3)   => bool::from_nums(args, Some(true)).map(Italic),
-            Some(0x4)   => bool::from_nums(args, Some(true)).map(Blink),
-            Some(0x5)   => bool::from_nums(args, Some(true)).map(InvertColors),
-            Some(0x6)   => bool::from_nums(args, Some(true)).map(Strikethrough),
-            Some(0x7)   => Some(Opacity(args.next().unwrap_or(0xff) as u8)),
-            Some(0x8)   => Color::from_nums(args, None).map(FgColor),
-            Some(0x9)   => Color::from_nums(args, None).map(BgColor),
-            Some(0xa)   => ConfigStyle::from_nums(args, None).map(Configured),
-            _           => default
+impl TextStyle {
+    /// Set one of the styles.
+    pub fn apply(&mut self, style: Style) {
+        match style {
+            Style::Underline(n)             => self.underline = n,
+            Style::Bold(on)                 => self.bold = on,
+            Style::Italic(on)               => self.italic = on,
+            Style::Blink(on)                => self.blink = on,
+            Style::InvertColors(on)         => self.inverted = on,
+            Style::Strikethrough(on)        => self.strikethrough = on,
+            Style::Opacity(n)               => self.opacity = n,
+            Style::FgColor(color)           => self.fg = color,
+            Style::BgColor(color)           => self.bg = color,
+            Style::Configured(config)       => self.configured = config,
+            Style::UnderlineShape(shape)    => self.underline_shape = shape,
+            Style::UnderlineColor(color)    => self.underline_color = color,
         }
     }
 
-    fn encode(&self) -> String {
-        match *self {
-            Underline(n)        => format!("1.{:x}", n),
-            Bold(flag)          => format!("2.{}", flag.encode()),
-            Italic(flag)        => format!("3.{}", flag.encode()),
-            Blink(flag)         => format!("4.{}", flag.encode()),
-            InvertColors(flag)  => format!("5.{}", flag.encode()),
-            Strikethrough(flag) => format!("6.{}", flag.encode()),
-            Opacity(n)          => format!("7.{:x}", n),
-            FgColor(color)      => format!("8.{}", color.encode()),
-            BgColor(color)      => format!("9.{}", color.encode()),
-            Configured(style)   => format!("a.{}", style.encode()),
+    /// Every style, one for each field, in the order of their tags.
+    pub fn styles(&self) -> [Style; 12] {
+        [
+            Style::Underline(self.underline),
+            Style::Bold(self.bold),
+            Style::Italic(self.italic),
+            Style::Blink(self.blink),
+            Style::InvertColors(self.inverted),
+            Style::Strikethrough(self.strikethrough),
+            Style::Opacity(self.opacity),
+            Style::FgColor(self.fg),
+            Style::BgColor(self.bg),
+            Style::Configured(self.configured),
+            Style::UnderlineShape(self.underline_shape),
+            Style::UnderlineColor(self.underline_color),
+        ]
+    }
+
+    /// The styles which must be applied to this style to turn it into another.
+    pub fn changes(&self, to: &TextStyle) -> Vec<Style> {
+        self.styles().iter().zip(to.styles().iter())
+            .filter(|&(from, to)| from != to)
+            .map(|(_, &to)| to)
+            .collect()
+    }
+}
+
+impl Default for TextStyle {
+    fn default() -> TextStyle {
+        TextStyle {
+            underline: 0,
+            underline_shape: UnderlineShape::Straight,
+            underline_color: Color::Default,
+            bold: false,
+            italic: false,
+            blink: false,
+            inverted: false,
+            strikethrough: false,
+            opacity: 0xff,
+            fg: Color::Default,
+            bg: Color::Default,
+            configured: DEFAULT_CONFIG_STYLE,
         }
     }
 }
@@ -60,30 +133,14 @@ impl Argument for Style {
 pub const DEFAULT_CONFIG_STYLE: ConfigStyle = ConfigStyle::Plain;
 
 /// Some means of identifying a preconfigured style provided by the user.
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd, Argument)]
 pub enum ConfigStyle {
+    #[tag = 1]
     Plain,
+    #[tag = 2]
     CodeGroup(CodeGroup),
 }
 
-impl Argument for ConfigStyle {
-    fn from_nums<T>(mut args: T, default: Option<ConfigStyle>) -> Option<ConfigStyle>
-    where T: Iterator<Item=u64> {
-        match args.next() {
-            Some(1) => Some(ConfigStyle::Plain),
-            Some(2) => CodeGroup::from_nums(args, None).map(ConfigStyle::CodeGroup),
-            _       => default
-        }
-    }
-
-    fn encode(&self) -> String {
-        match *self {
-            ConfigStyle::Plain              => String::from("1"),
-            ConfigStyle::CodeGroup(group)   => format!("2.{}", group.encode()),
-        }
-    }
-}
-
 impl Default for ConfigStyle {
     fn default() -> ConfigStyle {
         DEFAULT_CONFIG_STYLE
@@ -91,50 +148,27 @@ impl Default for ConfigStyle {
 }
 
 /// A kind of text found in source code, for syntax highlighting.
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd, Argument)]
 pub enum CodeGroup {
+    #[tag = 6]
     Comment,
+    #[tag = 7]
     Documentation,
+    #[tag = 8]
     Error,
+    #[tag = 2]
     Identifier,
+    #[tag = 1]
     Keyword,
+    #[tag = 4]
     Literal,
+    #[tag = 5]
     Macro,
+    #[tag = 0xa]
     Special,
+    #[tag = 3]
     Type,
+    #[tag = 9]
     Todo,
 }
 
-impl Argument for CodeGroup {
-    fn from_nums<T>(mut args: T, default: Option<CodeGroup>) -> Option<CodeGroup>
-    where T: Iterator<Item=u64> {
-        match args.next() {
-            Some(1)     => Some(Keyword),
-            Some(2)     => Some(Identifier),
-            Some(3)     => Some(Type),
-            Some(4)     => Some(Literal),
-            Some(5)     => Some(Macro),
-            Some(6)     => Some(Comment),
-            Some(7)     => Some(Documentation),
-            Some(8)     => Some(Error),
-            Some(9)     => Some(Todo),
-            Some(10)    => Some(Special),
-            _           => default,
-        }
-    }
-
-    fn encode(&self) -> String {
-        match *self {
-            Keyword         => String::from("1"),
-            Identifier      => String::from("2"),
-            Type            => String::from("3"),
-            Literal         => String::from("4"),
-            Macro           => String::from("5"),
-            Comment         => String::from("6"),
-            Documentation   => String::from("7"),
-            Error           => String::from("8"),
-            Todo            => String::from("9"),
-            Special         => String::from("a"),
-        }
-    }
-}
@@ -1,15 +1,53 @@
-use std::io::{self, Write};
+use std::io;
+use std::str;
 
-use cmds::EscCode;
+use ansi::{self, Translation};
+use cmds::{self, EscCode};
 
 pub trait Client {
-    fn write(&mut self, &EscCode) -> io::Result<()>;
+    fn write(&mut self, code: &dyn EscCode) -> io::Result<()>;
 }
 
 pub struct StdioClient;
 
 impl Client for StdioClient {
-    fn write(&mut self, code: &EscCode) -> io::Result<()> {
-        io::stdout().write_all(code.encode().as_bytes())
+    fn write(&mut self, code: &dyn EscCode) -> io::Result<()> {
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        code.encode_to(&mut stdout)
+    }
+}
+
+/// A client for terminals which do not understand notty escape sequences, which writes the
+/// closest ANSI equivalent of each command instead. The opcodes of commands which cannot be
+/// expressed in ANSI are collected in `unsupported`.
+pub struct AnsiClient<W> {
+    pub writer: W,
+    pub unsupported: Vec<u16>,
+}
+
+impl<W: io::Write> AnsiClient<W> {
+    pub fn new(writer: W) -> AnsiClient<W> {
+        AnsiClient {
+            writer: writer,
+            unsupported: Vec::new(),
+        }
+    }
+}
+
+impl<W: io::Write> Client for AnsiClient<W> {
+    fn write(&mut self, code: &dyn EscCode) -> io::Result<()> {
+        if code.is_noop() { return Ok(()) }
+        // The command is only known through its encoding, so it is decoded to be translated.
+        let mut body = Vec::with_capacity(code.body_len());
+        code.write_body(&mut body)?;
+        let translation = match str::from_utf8(&body).ok().and_then(cmds::decode_body) {
+            Some(cmd)   => ansi::translate(&cmd, &mut self.writer)?,
+            None        => Translation::Unsupported,
+        };
+        if translation == Translation::Unsupported {
+            self.unsupported.push(code.opcode());
+        }
+        Ok(())
     }
 }
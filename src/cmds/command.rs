@@ -0,0 +1,104 @@
+use std::io;
+
+use cmds::*;
+
+macro_rules! commands {
+    ($($cmd:ident),*) => {
+        /// Any one of the commands defined in this module.
+        ///
+        /// This is the type produced when escape sequences are decoded. Unlike a
+        /// `Box<dyn EscCode>`, it can be compared, cloned and matched on.
+        #[derive(Clone, Debug, Eq, PartialEq)]
+        pub enum Command {
+            $($cmd($cmd),)*
+        }
+
+        impl EscCode for Command {
+            fn opcode(&self) -> u16 {
+                match *self {
+                    $(Command::$cmd(ref cmd) => cmd.opcode(),)*
+                }
+            }
+
+            fn is_noop(&self) -> bool {
+                match *self {
+                    $(Command::$cmd(ref cmd) => cmd.is_noop(),)*
+                }
+            }
+
+            fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+                match *self {
+                    $(Command::$cmd(ref cmd) => cmd.write_args(w),)*
+                }
+            }
+
+            fn each_attachment(&self, f: &mut dyn FnMut(&[u8]) -> io::Result<()>)
+                    -> io::Result<()> {
+                match *self {
+                    $(Command::$cmd(ref cmd) => cmd.each_attachment(f),)*
+                }
+            }
+        }
+
+        $(
+            impl From<$cmd> for Command {
+                fn from(cmd: $cmd) -> Command {
+                    Command::$cmd(cmd)
+                }
+            }
+        )*
+    }
+}
+
+commands! {
+    PutMedia, PutMediaAt,
+    Move, ScrollScreen,
+    Erase, RemoveChars, RemoveRows, InsertBlank, InsertRows,
+    SetTextStyle, DefaultTextStyle, SetCursorStyle, DefaultCursorStyle, SetStyleInArea,
+    DefaultStyleInArea,
+    AddToolTip, AddDropDown, RemoveToolTip,
+    BeginHyperlink, EndHyperlink, AddHyperlink,
+    SetTitle,
+    PushPanel, PopPanel, SplitPanel, UnsplitPanel, AdjustPanelSplit, RotateSectionDown,
+    RotateSectionUp, SwitchActiveSection,
+    SetInputMode, HoldForInput
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::mpsc;
+    use std::thread;
+
+    use args::{Area, Coords};
+    use cmds::*;
+
+    #[test]
+    fn boxed_commands() {
+        let frame: Vec<Box<dyn EscCode + Send>> = vec![
+            Box::new(Erase::new(Area::WholeScreen)),
+            Box::new(SetTitle(String::from("title"))),
+            Box::new(Command::from(RemoveToolTip(Coords { x: 1, y: 2 }))),
+        ];
+        let opcodes: Vec<u16> = frame.iter().map(|cmd| cmd.opcode()).collect();
+        assert_eq!(opcodes, [0x20, 0x40, 0x54]);
+        let decoded: Vec<Command> = frame.iter().flat_map(|cmd| decode(&cmd.encode())).collect();
+        assert_eq!(decoded, [
+            Command::from(Erase::new(Area::WholeScreen)),
+            Command::from(SetTitle(String::from("title"))),
+            Command::from(RemoveToolTip(Coords { x: 1, y: 2 })),
+        ]);
+    }
+
+    #[test]
+    fn send_commands_between_threads() {
+        let (tx, rx) = mpsc::channel();
+        let frame: Vec<Command> = vec![HoldForInput.into(), PopPanel(Some(1)).into()];
+        let expected = frame.clone();
+        thread::spawn(move || tx.send(frame).unwrap()).join().unwrap();
+        let frame = rx.recv().unwrap();
+        assert_eq!(frame, expected);
+        assert_eq!(frame[1].encode(), "\x1b_[61;1\u{9c}");
+    }
+
+}
@@ -0,0 +1,515 @@
+use std::str;
+
+use args::{Argument, MediaPosition};
+use cmds::*;
+use error::{DecodeError, ErrorKind};
+use MediaFormat;
+
+/// Decode a complete escape sequence, as produced by `EscCode::encode` or an `Encoder`, into a
+/// command.
+///
+/// Returns `None` if the sequence is not correctly framed, has an unknown opcode, or if any of
+/// its required arguments or attachments are missing or invalid.
+pub fn decode(code: &str) -> Option<Command> {
+    Decoder::lenient().decode(code).ok()
+}
+
+/// Decode the body of an escape sequence - the text between the `APC [` introducer and the
+/// string terminator - into a command.
+pub fn decode_body(body: &str) -> Option<Command> {
+    Decoder::lenient().decode_body(body).ok()
+}
+
+/// Decode a complete escape sequence into a command, returning an error describing why if it
+/// cannot be decoded. Arguments are decoded with `Argument::try_decode`.
+pub fn try_decode(code: &str) -> Result<Command, DecodeError> {
+    Decoder::new().decode(code)
+}
+
+/// Decode the body of an escape sequence into a command, returning an error describing why if
+/// it cannot be decoded.
+pub fn try_decode_body(body: &str) -> Result<Command, DecodeError> {
+    Decoder::new().decode_body(body)
+}
+
+/// A configurable decoder for escape sequences.
+///
+/// Decoding never panics, whatever the input. The limits on a decoder bound how much memory
+/// decoding a sequence can use; a decoder created with `Decoder::untrusted` has limits suitable
+/// for decoding the output of programs which are not trusted.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Decoder {
+    /// The maximum length of the body of a sequence, in bytes.
+    pub max_len: usize,
+    /// The maximum number of arguments to a command.
+    pub max_args: usize,
+    /// The maximum number of attachments to a command.
+    pub max_attachments: usize,
+    /// The maximum length of a single attachment, in bytes of base64.
+    pub max_attachment_len: usize,
+    /// If true, arguments are decoded with `Argument::decode` rather than `Argument::try_decode`.
+    pub lenient: bool,
+    /// If true (and the decoder is not lenient), arguments are decoded with
+    /// `Argument::decode_strict`, rejecting values which are too large for their fields.
+    pub strict: bool,
+}
+
+impl Decoder {
+    /// Create a decoder without limits, which decodes arguments with `Argument::try_decode`.
+    pub fn new() -> Decoder {
+        Decoder {
+            max_len: usize::MAX,
+            max_args: usize::MAX,
+            max_attachments: usize::MAX,
+            max_attachment_len: usize::MAX,
+            lenient: false,
+            strict: false,
+        }
+    }
+
+    /// Create a decoder without limits, which decodes arguments with `Argument::decode`.
+    pub fn lenient() -> Decoder {
+        Decoder { lenient: true, ..Decoder::new() }
+    }
+
+    /// Create a decoder with limits suitable for untrusted input, which decodes arguments with
+    /// `Argument::decode_strict`.
+    pub fn untrusted() -> Decoder {
+        Decoder {
+            max_len: 1 << 24,
+            max_args: 16,
+            max_attachments: 256,
+            max_attachment_len: 1 << 24,
+            lenient: false,
+            strict: true,
+        }
+    }
+
+    /// Decode a complete escape sequence into a command.
+    ///
+    /// The sequence may be framed in any of the ways an `Encoder` can frame it.
+    pub fn decode(&self, code: &str) -> Result<Command, DecodeError> {
+        self.decode_bytes(code.as_bytes())
+    }
+
+    /// Decode a complete escape sequence, which may use 8-bit controls and so not be valid
+    /// UTF-8, into a command.
+    pub fn decode_bytes(&self, code: &[u8]) -> Result<Command, DecodeError> {
+        match unwrap_tmux(code) {
+            Some(inner) => {
+                if inner.len() > self.max_len.saturating_add(8) {
+                    return Err(DecodeError::new(ErrorKind::LimitExceeded))
+                }
+                self.decode_sequence(&unescape_tmux(inner))
+            }
+            None        => self.decode_sequence(code),
+        }
+    }
+
+    fn decode_sequence(&self, code: &[u8]) -> Result<Command, DecodeError> {
+        let body = strip_introducer(code).and_then(strip_terminator)
+                                         .and_then(|body| str::from_utf8(body).ok());
+        match body {
+            Some(body)  => self.decode_body(body),
+            None        => Err(DecodeError::new(ErrorKind::Framing)),
+        }
+    }
+
+    /// Decode the body of an escape sequence into a command.
+    pub fn decode_body(&self, body: &str) -> Result<Command, DecodeError> {
+        if body.len() > self.max_len {
+            return Err(DecodeError::new(ErrorKind::LimitExceeded))
+        }
+        let mut parts = body.split('#');
+        let mut args = parts.next().unwrap_or("").split(';');
+        let opcode = match args.next() {
+            Some(token) if !token.is_empty()    => u16::from_str_radix(token, 16).map_err(|_| {
+                DecodeError::new(ErrorKind::InvalidDigit).with_token(token)
+            })?,
+            _                                   => {
+                return Err(DecodeError::new(ErrorKind::Missing))
+            }
+        };
+        let args = Args {
+            opcode: opcode,
+            args: args.take(self.max_args.saturating_add(1)).collect(),
+            decoder: self,
+        };
+        if args.len() > self.max_args {
+            return Err(DecodeError::new(ErrorKind::LimitExceeded).in_command(opcode))
+        }
+        let mut attachments = Vec::new();
+        for attachment in parts {
+            if attachments.len() == self.max_attachments
+                    || attachment.len() > self.max_attachment_len {
+                return Err(DecodeError::new(ErrorKind::LimitExceeded).in_command(opcode))
+            }
+            attachments.push(::base64::u8de(attachment.as_bytes()).map_err(|_| {
+                DecodeError::new(ErrorKind::InvalidAttachment).in_command(opcode)
+            })?);
+        }
+
+        let cmd = match opcode {
+            0x14 => {
+                let (fmt, data) = media(opcode, attachments)?;
+                Command::PutMedia(PutMedia::new(args.get(0, None)?,
+                                                args.get(1, None)?,
+                                                args.get(2, Some(MediaPosition::default()))?,
+                                                fmt, data))
+            }
+            0x15 => {
+                let (fmt, data) = media(opcode, attachments)?;
+                Command::PutMediaAt(PutMediaAt::new(args.get(0, None)?,
+                                                    args.get(1, None)?,
+                                                    args.get(2, None)?,
+                                                    args.get(3,
+                                                             Some(MediaPosition::default()))?,
+                                                    fmt, data))
+            }
+            0x18 => Command::Move(Move::new(args.get(0, None)?)),
+            0x19 => Command::ScrollScreen(ScrollScreen::new(args.get(0, None)?,
+                                                            args.get(1, Some(1))?)),
+            0x20 => Command::Erase(Erase::new(args.get(0, None)?)),
+            0x21 => Command::RemoveChars(RemoveChars::new(args.get(0, Some(1))?)),
+            0x22 => Command::RemoveRows(RemoveRows::new(args.get(0, Some(1))?,
+                                                        args.get(1, Some(true))?)),
+            0x26 => Command::InsertBlank(InsertBlank::new(args.get(0, Some(1))?)),
+            0x27 => Command::InsertRows(InsertRows::new(args.get(0, Some(1))?,
+                                                        args.get(1, Some(true))?)),
+            0x30 if args.len() == 0 => Command::DefaultTextStyle(DefaultTextStyle),
+            0x30 => Command::SetTextStyle(SetTextStyle(args.rest(0)?)),
+            0x31 if args.len() == 0 => Command::DefaultCursorStyle(DefaultCursorStyle),
+            0x31 => Command::SetCursorStyle(SetCursorStyle(args.rest(0)?)),
+            0x32 if args.len() < 2  => {
+                Command::DefaultStyleInArea(DefaultStyleInArea(args.get(0, None)?))
+            }
+            0x32 => Command::SetStyleInArea(SetStyleInArea(args.get(0, None)?, args.rest(1)?)),
+            0x40 => Command::SetTitle(SetTitle(text(opcode, attachments.into_iter().next())?)),
+            0x50 => {
+                let tip = text(opcode, attachments.into_iter().next())?;
+                Command::AddToolTip(AddToolTip(args.get(0, None)?, tip))
+            }
+            0x51 => {
+                let options = attachments.into_iter().map(|opt| text(opcode, Some(opt)))
+                                         .collect::<Result<Vec<String>, _>>()?;
+                Command::AddDropDown(AddDropDown::new(args.get(0, None)?, options))
+            }
+            0x54 => Command::RemoveToolTip(RemoveToolTip(args.get(0, None)?)),
+            0x55 => {
+                let url = text(opcode, attachments.into_iter().next())?;
+                Command::BeginHyperlink(BeginHyperlink::new(args.opt(0)?, url))
+            }
+            0x56 => Command::EndHyperlink(EndHyperlink),
+            0x57 => {
+                let url = text(opcode, attachments.into_iter().next())?;
+                Command::AddHyperlink(AddHyperlink(args.get(0, None)?, url))
+            }
+            0x60 => Command::PushPanel(PushPanel(args.opt(0)?, args.opt(1)?)),
+            0x61 => Command::PopPanel(PopPanel(args.opt(0)?)),
+            0x62 => Command::SplitPanel(SplitPanel::new(args.get(0, None)?,
+                                                        args.get(1, None)?,
+                                                        args.get(2, None)?,
+                                                        args.opt(3)?,
+                                                        args.opt(4)?,
+                                                        args.opt(5)?,
+                                                        args.opt(6)?)),
+            0x63 => Command::UnsplitPanel(UnsplitPanel::new(args.get(0, None)?,
+                                                            args.get(1, None)?)),
+            0x64 => Command::AdjustPanelSplit(AdjustPanelSplit::new(args.get(0, None)?,
+                                                                    args.get(1, None)?,
+                                                                    args.get(2, None)?)),
+            0x65 => Command::RotateSectionDown(RotateSectionDown(args.opt(0)?)),
+            0x66 => Command::RotateSectionUp(RotateSectionUp(args.opt(0)?)),
+            0x67 => Command::SwitchActiveSection(SwitchActiveSection(args.get(0, None)?)),
+            0x80 => Command::SetInputMode(SetInputMode(args.get(0, None)?)),
+            0x87 => Command::HoldForInput(HoldForInput),
+            _    => {
+                return Err(DecodeError::new(ErrorKind::UnknownOpcode).in_command(opcode))
+            }
+        };
+        Ok(cmd)
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Decoder {
+        Decoder::new()
+    }
+}
+
+// The arguments of a command being decoded.
+struct Args<'a> {
+    opcode: u16,
+    args: Vec<&'a str>,
+    decoder: &'a Decoder,
+}
+
+impl<'a> Args<'a> {
+    fn len(&self) -> usize {
+        self.args.len()
+    }
+
+    // Decode a required argument.
+    fn get<T: Argument>(&self, n: usize, default: Option<T>) -> Result<T, DecodeError> {
+        let arg = self.args.get(n).cloned();
+        if self.decoder.lenient {
+            T::decode(arg, default).ok_or_else(|| {
+                DecodeError::new(ErrorKind::Missing).in_arg(self.opcode, n)
+            })
+        } else if self.decoder.strict {
+            T::decode_strict(arg, default).map_err(|err| err.in_arg(self.opcode, n))
+        } else {
+            T::try_decode(arg, default).map_err(|err| err.in_arg(self.opcode, n))
+        }
+    }
+
+    // Decode every argument from the nth onwards, each of which is required.
+    fn rest<T: Argument>(&self, n: usize) -> Result<Vec<T>, DecodeError> {
+        (n..self.len()).map(|i| self.get(i, None)).collect()
+    }
+
+    // Decode an optional argument, which is None if it is absent.
+    fn opt<T: Argument>(&self, n: usize) -> Result<Option<T>, DecodeError> {
+        match self.args.get(n) {
+            Some(&arg) if self.decoder.lenient  => Ok(T::decode(Some(arg), None)),
+            Some(arg) if !arg.is_empty()        => self.get(n, None).map(Some),
+            _                                   => Ok(None),
+        }
+    }
+}
+
+fn text(opcode: u16, attachment: Option<Vec<u8>>) -> Result<String, DecodeError> {
+    match attachment {
+        Some(data)  => String::from_utf8(data).map_err(|_| {
+            DecodeError::new(ErrorKind::InvalidAttachment).in_command(opcode)
+        }),
+        None        => Err(DecodeError::new(ErrorKind::Missing).in_command(opcode)),
+    }
+}
+
+fn media(opcode: u16, mut attachments: Vec<Vec<u8>>)
+        -> Result<(MediaFormat, Vec<u8>), DecodeError> {
+    let data = match (attachments.pop(), attachments.pop()) {
+        (Some(data), Some(mime)) if attachments.is_empty()  => {
+            str::from_utf8(&mime).ok().and_then(MediaFormat::from_mime).map(|fmt| (fmt, data))
+        }
+        _                                                   => None,
+    };
+    data.ok_or_else(|| DecodeError::new(ErrorKind::InvalidAttachment).in_command(opcode))
+}
+
+// The forms of the introducer and terminator accepted by the decoder: 7-bit, UTF-8 and 8-bit.
+const INTRODUCERS: &'static [&'static [u8]] = &[b"\x1b_[", b"\xc2\x9f[", b"\x9f["];
+const TERMINATORS: &'static [&'static [u8]] = &[b"\x1b\\", b"\xc2\x9c", b"\x9c"];
+
+fn strip_introducer(code: &[u8]) -> Option<&[u8]> {
+    INTRODUCERS.iter().find(|intro| code.starts_with(intro)).map(|intro| &code[intro.len()..])
+}
+
+fn strip_terminator(code: &[u8]) -> Option<&[u8]> {
+    TERMINATORS.iter().find(|term| code.ends_with(term))
+                      .map(|term| &code[..code.len() - term.len()])
+}
+
+// Remove the tmux passthrough wrapper from a sequence, if it has one.
+fn unwrap_tmux(code: &[u8]) -> Option<&[u8]> {
+    let (prefix, suffix): (&[u8], &[u8]) = (b"\x1bPtmux;", b"\x1b\\");
+    if code.len() >= prefix.len() + suffix.len() && code.starts_with(prefix)
+            && code.ends_with(suffix) {
+        Some(&code[prefix.len()..code.len() - suffix.len()])
+    } else { None }
+}
+
+// Replace each doubled ESC inside a tmux passthrough sequence with a single ESC.
+fn unescape_tmux(inner: &[u8]) -> Vec<u8> {
+    let mut code = Vec::with_capacity(inner.len());
+    let mut escaped = false;
+    for &byte in inner {
+        if byte == 0x1b && !escaped {
+            escaped = true;
+            continue
+        }
+        escaped = false;
+        code.push(byte);
+    }
+    code
+}
+
+#[cfg(test)]
+mod tests {
+
+    use args::*;
+    use cmds::*;
+    use error::ErrorKind;
+    use MediaFormat;
+
+    fn commands() -> Vec<Command> {
+        vec![
+            PutMedia::new(4, 2, MediaPosition::Fit, MediaFormat::Png, vec![0, 1, 2, 0xff]).into(),
+            PutMediaAt::new(Coords { x: 1, y: 2 }, 4, 2, MediaPosition::Tile, MediaFormat::Gif,
+                            vec![]).into(),
+            Move::new(Movement::To(Direction::Down, 3, true)).into(),
+            ScrollScreen::new(Direction::Up, 0x10).into(),
+            Erase::new(Area::Bound(Region::new(0, 0, 4, 4))).into(),
+            RemoveChars::new(2).into(),
+            RemoveRows::new(3, false).into(),
+            InsertBlank::new(1).into(),
+            InsertRows::new(0xff, true).into(),
+            SetTextStyle(vec![Style::FgColor(Color::True(1, 2, 3))]).into(),
+            DefaultTextStyle.into(),
+            SetCursorStyle(vec![Style::Blink(true), Style::Opacity(0x80)]).into(),
+            DefaultCursorStyle.into(),
+            SetStyleInArea(Area::CursorRow, vec![Style::Bold(true)]).into(),
+            DefaultStyleInArea(Area::WholeScreen).into(),
+            AddToolTip(Coords { x: 7, y: 0 }, String::from("tip; with #special chars")).into(),
+            AddDropDown::new(Coords { x: 0, y: 1 }, vec![String::from("a"), String::from("b")])
+                .into(),
+            RemoveToolTip(Coords { x: 7, y: 0 }).into(),
+            BeginHyperlink::new(Some(0x1f), String::from("https://example.com/#a;b")).into(),
+            BeginHyperlink::new(None, String::from("file:///tmp/log")).into(),
+            EndHyperlink.into(),
+            AddHyperlink(Area::CursorRow, String::from("https://example.com")).into(),
+            SetTitle(String::from("title")).into(),
+            PushPanel(Some(2), Some(false)).into(),
+            PushPanel(None, None).into(),
+            PopPanel(Some(0xab)).into(),
+            SplitPanel::new(1, 2, SplitKind::Vertical(0x20), Some(SaveGrid::Right),
+                            Some(ResizeRule::MaxRightBottom), Some(3), Some(true)).into(),
+            SplitPanel::new(1, 2, SplitKind::Horizontal(4), None, None, None, None).into(),
+            UnsplitPanel::new(SaveGrid::Left, 4).into(),
+            AdjustPanelSplit::new(SplitKind::Horizontal(8), ResizeRule::Percentage, 5).into(),
+            RotateSectionDown(None).into(),
+            RotateSectionUp(Some(6)).into(),
+            SwitchActiveSection(7).into(),
+            SetInputMode(InputSettings::Notty(())).into(),
+            HoldForInput.into(),
+        ]
+    }
+
+    #[test]
+    fn decode_encoded_commands() {
+        for cmd in commands() {
+            assert_eq!(decode(&cmd.encode()), Some(cmd));
+        }
+    }
+
+    #[test]
+    fn try_decode_encoded_commands() {
+        for cmd in commands() {
+            assert_eq!(try_decode(&cmd.encode()), Ok(cmd));
+        }
+    }
+
+    #[test]
+    fn encode_into_writer() {
+        for cmd in commands() {
+            let mut buf = Vec::new();
+            cmd.encode_to(&mut buf).unwrap();
+            assert_eq!(buf, cmd.encode().into_bytes());
+            assert_eq!(cmd.encoded_len(), buf.len());
+        }
+    }
+
+    #[test]
+    fn decode_any_framing() {
+        let forms = [Control::SevenBit, Control::EightBit, Control::Utf8];
+        for cmd in commands() {
+            for &introducer in &forms {
+                for &terminator in &forms {
+                    for &passthrough in &[None, Some(Passthrough::Tmux)] {
+                        let encoder = Encoder {
+                            introducer: introducer,
+                            terminator: terminator,
+                            passthrough: passthrough,
+                        };
+                        let code = encoder.encode(&cmd);
+                        assert_eq!(encoder.encoded_len(&cmd), code.len());
+                        assert_eq!(Decoder::new().decode_bytes(&code), Ok(cmd.clone()));
+                    }
+                }
+            }
+        }
+        assert_eq!(Encoder::seven_bit().encode(&HoldForInput), b"\x1b_[87\x1b\\");
+        assert_eq!(Encoder { passthrough: Some(Passthrough::Tmux), ..Encoder::seven_bit() }
+                       .encode(&HoldForInput),
+                   b"\x1bPtmux;\x1b\x1b_[87\x1b\x1b\\\x1b\\");
+        assert_eq!(decode("\u{9f}[87\x1b\\"), Some(HoldForInput.into()));
+    }
+
+    #[test]
+    fn try_decode_errors() {
+        let err = try_decode_body("32;1;1.3").unwrap_err();
+        assert_eq!((err.kind, err.opcode, err.arg, err.token),
+                   (ErrorKind::OutOfRange, Some(0x32), Some(1), Some(String::from("3"))));
+        let err = try_decode_body("19;5").unwrap_err();
+        assert_eq!((err.kind, err.opcode, err.arg), (ErrorKind::UnknownTag, Some(0x19), Some(0)));
+        let err = try_decode_body("62;1").unwrap_err();
+        assert_eq!((err.kind, err.opcode, err.arg), (ErrorKind::Missing, Some(0x62), Some(1)));
+        assert_eq!(try_decode_body("ff").unwrap_err().kind, ErrorKind::UnknownOpcode);
+        assert_eq!(try_decode_body("4x").unwrap_err().kind, ErrorKind::InvalidDigit);
+        assert_eq!(try_decode_body("40#!").unwrap_err().kind, ErrorKind::InvalidAttachment);
+        assert_eq!(try_decode("40#AA==").unwrap_err().kind, ErrorKind::Framing);
+    }
+
+    #[test]
+    fn decode_style_lists() {
+        let styles = vec![Style::Bold(true), Style::Italic(true),
+                          Style::FgColor(Color::Palette(1))];
+        assert_eq!(SetTextStyle(vec![Style::Bold(true)]).encode(), "\x1b_[30;2.1\u{9c}");
+        assert_eq!(SetTextStyle(styles.clone()).encode(), "\x1b_[30;2.1;3.1;8.1.1\u{9c}");
+        assert_eq!(try_decode_body("30;2.1;3.1;8.1.1"), Ok(SetTextStyle(styles.clone()).into()));
+        assert_eq!(try_decode_body("31;2.1;3.1;8.1.1"), Ok(SetCursorStyle(styles.clone()).into()));
+        assert_eq!(try_decode_body("32;6;2.1;3.1;8.1.1"),
+                   Ok(SetStyleInArea(Area::WholeScreen, styles).into()));
+        let err = try_decode_body("30;2.1;f").unwrap_err();
+        assert_eq!((err.kind, err.opcode, err.arg), (ErrorKind::UnknownTag, Some(0x30), Some(1)));
+
+        // Empty lists are not encoded, rather than being encoded as resets.
+        let empty: [Command; 3] = [SetTextStyle(vec![]).into(), SetCursorStyle(vec![]).into(),
+                                   SetStyleInArea(Area::CursorRow, vec![]).into()];
+        for cmd in &empty {
+            assert_eq!(cmd.encode(), "");
+            assert_eq!(cmd.encoded_len(), 0);
+            assert_eq!(Encoder::seven_bit().encode(cmd), b"");
+        }
+    }
+
+    #[test]
+    fn strict_decoder() {
+        let decoder = Decoder { strict: true, ..Decoder::new() };
+        let err = decoder.decode_body("30;8.1.100").unwrap_err();
+        assert_eq!((err.kind, err.opcode, err.arg, err.field),
+                   (ErrorKind::Overflow, Some(0x30), Some(0), Some("palette")));
+        assert_eq!(try_decode_body("30;8.1.100"),
+                   Ok(SetTextStyle(vec![Style::FgColor(Color::Palette(0))]).into()));
+    }
+
+    #[test]
+    fn decoder_limits() {
+        let decoder = Decoder { max_len: 24, max_args: 2, max_attachments: 1,
+                                max_attachment_len: 8, lenient: false, strict: false };
+        let error = |body: &str| decoder.decode_body(body).unwrap_err().kind;
+        assert_eq!(error("30;1;2;3"), ErrorKind::LimitExceeded);
+        assert_eq!(error("51;0.0#YQ==#Yg=="), ErrorKind::LimitExceeded);
+        assert_eq!(error("40#dGl0bGUgdGl0bGU="), ErrorKind::LimitExceeded);
+        assert_eq!(error("40#dGl0bGU=;;;;;;;;;;;;;;;;;;;;;;"), ErrorKind::LimitExceeded);
+        assert_eq!(decoder.decode_body("40#dGl0bGU="), Ok(SetTitle(String::from("title")).into()));
+    }
+
+    #[test]
+    fn decode_rejects_bad_framing() {
+        let code = Erase::new(Area::CursorCell).encode();
+        assert!(decode(&code[1..]).is_none());
+        assert!(decode(&code[..code.len() - 2]).is_none());
+        assert!(decode("\x1b_[\u{9c}").is_none());
+    }
+
+    #[test]
+    fn decode_rejects_bad_commands() {
+        assert!(decode_body("ff").is_none());
+        assert!(decode_body("18").is_none());
+        assert!(decode_body("40#not base64!").is_none());
+        assert!(decode_body("14;1;1;2#aW1hZ2UvYm1w#AA==").is_none());
+    }
+
+}
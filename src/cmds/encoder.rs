@@ -0,0 +1,137 @@
+use std::io;
+
+use cmds::EscCode;
+
+const ESC: u8 = 0x1b;
+
+// The final byte of the 7-bit form and the 8-bit form of the controls which frame a sequence.
+const APC: (u8, u8) = (b'_', 0x9f);
+const ST: (u8, u8) = (b'\\', 0x9c);
+
+/// The form in which a C1 control is written.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Control {
+    /// `ESC` followed by a printable character, such as `ESC \` for the string terminator.
+    SevenBit,
+    /// A single byte in the range `0x80` to `0x9f`. Sequences containing these are not valid
+    /// UTF-8.
+    EightBit,
+    /// The UTF-8 encoding of the control's code point, such as `0xc2 0x9c` for the string
+    /// terminator.
+    Utf8,
+}
+
+impl Control {
+    fn len(self, passthrough: Option<Passthrough>) -> usize {
+        match (self, passthrough) {
+            (Control::SevenBit, Some(Passthrough::Tmux))    => 3,
+            (Control::SevenBit, None) | (Control::Utf8, _)  => 2,
+            (Control::EightBit, _)                          => 1,
+        }
+    }
+
+    fn write(self, w: &mut dyn io::Write, (final_byte, c1): (u8, u8),
+             passthrough: Option<Passthrough>) -> io::Result<()> {
+        match (self, passthrough) {
+            (Control::SevenBit, Some(Passthrough::Tmux))    => w.write_all(&[ESC, ESC, final_byte]),
+            (Control::SevenBit, None)                       => w.write_all(&[ESC, final_byte]),
+            (Control::EightBit, _)                          => w.write_all(&[c1]),
+            (Control::Utf8, _)                              => w.write_all(&[0xc2, c1]),
+        }
+    }
+}
+
+/// A wrapper around a sequence which allows it to pass through a terminal multiplexer to the
+/// terminal outside of it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Passthrough {
+    /// Wrap the sequence as `ESC P tmux; ... ESC \`, doubling each `ESC` inside of it. tmux only
+    /// passes these sequences through if its `allow-passthrough` option is set.
+    Tmux,
+}
+
+impl Passthrough {
+    fn prefix(self) -> &'static [u8] {
+        match self {
+            Passthrough::Tmux   => b"\x1bPtmux;",
+        }
+    }
+
+    fn suffix(self) -> &'static [u8] {
+        match self {
+            Passthrough::Tmux   => b"\x1b\\",
+        }
+    }
+}
+
+/// A configurable encoder for escape sequences, which controls how sequences are framed.
+///
+/// Every form of framing produced by an encoder is accepted by `Decoder::decode_bytes`; sequences
+/// which are wrapped for passthrough are unwrapped by the multiplexer before they reach the
+/// terminal.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Encoder {
+    /// The form of the `APC` control which introduces each sequence.
+    pub introducer: Control,
+    /// The form of the `ST` control which terminates each sequence.
+    pub terminator: Control,
+    /// The passthrough wrapper around each sequence, if any.
+    pub passthrough: Option<Passthrough>,
+}
+
+impl Encoder {
+    /// Create an encoder which frames sequences in the same way as `EscCode::encode`: introduced
+    /// by `ESC _ [` and terminated by the UTF-8 encoding of the string terminator.
+    pub fn new() -> Encoder {
+        Encoder {
+            introducer: Control::SevenBit,
+            terminator: Control::Utf8,
+            passthrough: None,
+        }
+    }
+
+    /// Create an encoder which frames sequences using only 7-bit controls, terminating them with
+    /// `ESC \`.
+    pub fn seven_bit() -> Encoder {
+        Encoder { terminator: Control::SevenBit, ..Encoder::new() }
+    }
+
+    /// Encode a command as an escape sequence.
+    pub fn encode<E>(&self, code: &E) -> Vec<u8> where E: EscCode + ?Sized {
+        let mut buf = Vec::with_capacity(self.encoded_len(code));
+        let _ = self.encode_to(&mut buf, code);
+        buf
+    }
+
+    /// Write a command as an escape sequence, without any intermediate allocations. Nothing is
+    /// written for a command which does nothing.
+    pub fn encode_to<E>(&self, w: &mut dyn io::Write, code: &E) -> io::Result<()>
+    where E: EscCode + ?Sized {
+        if code.is_noop() { return Ok(()) }
+        if let Some(passthrough) = self.passthrough {
+            w.write_all(passthrough.prefix())?;
+        }
+        self.introducer.write(w, APC, self.passthrough)?;
+        w.write_all(b"[")?;
+        code.write_body(w)?;
+        self.terminator.write(w, ST, self.passthrough)?;
+        if let Some(passthrough) = self.passthrough {
+            w.write_all(passthrough.suffix())?;
+        }
+        Ok(())
+    }
+
+    /// The length in bytes of the escape sequence for a command.
+    pub fn encoded_len<E>(&self, code: &E) -> usize where E: EscCode + ?Sized {
+        if code.is_noop() { return 0 }
+        let wrapper = self.passthrough.map_or(0, |p| p.prefix().len() + p.suffix().len());
+        wrapper + self.introducer.len(self.passthrough) + 1 + code.body_len()
+            + self.terminator.len(self.passthrough)
+    }
+}
+
+impl Default for Encoder {
+    fn default() -> Encoder {
+        Encoder::new()
+    }
+}
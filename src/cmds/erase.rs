@@ -1,7 +1,10 @@
+use std::io;
+
 use args::{Argument, Area};
 use cmds::EscCode;
 
 /// Command to erase all content in an area, replacing it with empty cells.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Erase {
     pub area: Area,
 }
@@ -13,15 +16,16 @@ impl Erase {
 }
 
 impl EscCode for Erase {
-    const OPCODE: u16 = 0x20;
-    fn args(&self) -> Vec<String> {
-        encode_args![self.area]
+    fn opcode(&self) -> u16 { 0x20 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => self.area]
     }
 }
 
 /// Command to remove characters, beginning at the cursor. This is not the same as erasing these
 /// characters: characters to the right of them will move to the left to fill their position
 /// (though characters from the next line will not wrap into this line).
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct RemoveChars {
     pub count: u32,
 }
@@ -33,15 +37,16 @@ impl RemoveChars {
 }
 
 impl EscCode for RemoveChars {
-    const OPCODE: u16 = 0x21;
-    fn args(&self) -> Vec<String> {
-        encode_args![self.count]
+    fn opcode(&self) -> u16 { 0x21 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => self.count]
     }
 }
 
 /// Command to remove rows, beginning at the cursor. Lines below them will move upward to replace
 /// them; the boolean determines if the row the cursor is in is included in the content to be
 /// removed.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct RemoveRows {
     pub count: u32,
     pub include: bool,
@@ -57,14 +62,15 @@ impl RemoveRows {
 }
 
 impl EscCode for RemoveRows {
-    const OPCODE: u16 = 0x22;
-    fn args(&self) -> Vec<String> {
-        encode_args![self.count, self.include]
+    fn opcode(&self) -> u16 { 0x22 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => self.count, self.include]
     }
 }
 
 /// Insert blank characters at the cursor, this will insert empty cells, and move the character
 /// at the cursor over to the right (it will not wrap, but instead be removed from the screen).
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct InsertBlank {
     pub count: u32
 }
@@ -78,14 +84,15 @@ impl InsertBlank {
 }
 
 impl EscCode for InsertBlank {
-    const OPCODE: u16 = 0x26;
-    fn args(&self) -> Vec<String> {
-        encode_args![self.count]
+    fn opcode(&self) -> u16 { 0x26 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => self.count]
     }
 }
 
 /// Insert blank rows at the cursor. The boolean determines if they are inserted above the cursor
 /// row, or below.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct InsertRows {
     pub count: u32,
     pub include: bool
@@ -101,8 +108,8 @@ impl InsertRows {
 }
 
 impl EscCode for InsertRows {
-    const OPCODE: u16 = 0x27;
-    fn args(&self) -> Vec<String> {
-        encode_args![self.count, self.include]
+    fn opcode(&self) -> u16 { 0x27 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => self.count, self.include]
     }
 }
@@ -0,0 +1,53 @@
+use std::io;
+
+use args::{Argument, Area};
+use cmds::EscCode;
+
+/// Link all content written from now on to a URL, until the next `BeginHyperlink` or
+/// `EndHyperlink`.
+///
+/// Content linked with the same id is part of the same link, even when it is not contiguous,
+/// such as a link which is written across several rows.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BeginHyperlink {
+    pub id: Option<u64>,
+    pub url: String,
+}
+
+impl BeginHyperlink {
+    pub fn new(id: Option<u64>, url: String) -> BeginHyperlink {
+        BeginHyperlink { id: id, url: url }
+    }
+}
+
+impl EscCode for BeginHyperlink {
+    fn opcode(&self) -> u16 { 0x55 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => ?self.id]
+    }
+    fn each_attachment(&self, f: &mut dyn FnMut(&[u8]) -> io::Result<()>) -> io::Result<()> {
+        f(self.url.as_bytes())
+    }
+}
+
+/// Stop linking content written from now on.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EndHyperlink;
+
+impl EscCode for EndHyperlink {
+    fn opcode(&self) -> u16 { 0x56 }
+}
+
+/// Link the content in a given area of the grid to a URL.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AddHyperlink(pub Area, pub String);
+
+impl EscCode for AddHyperlink {
+    fn opcode(&self) -> u16 { 0x57 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => self.0]
+    }
+    fn each_attachment(&self, f: &mut dyn FnMut(&[u8]) -> io::Result<()>) -> io::Result<()> {
+        f(self.1.as_bytes())
+    }
+}
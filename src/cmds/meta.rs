@@ -1,29 +1,34 @@
+use std::io;
+
 use args::{Argument, InputSettings};
 use cmds::EscCode;
 
 /// Set the title of the screen.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct SetTitle(pub String);
 
 impl EscCode for SetTitle {
-    const OPCODE: u16 = 0x40;
-    fn attachments(&self) -> Vec<Vec<u8>> {
-        vec![self.0.clone().into_bytes()]
+    fn opcode(&self) -> u16 { 0x40 }
+    fn each_attachment(&self, f: &mut dyn FnMut(&[u8]) -> io::Result<()>) -> io::Result<()> {
+        f(self.0.as_bytes())
     }
 }
 
 /// Set the input mode for the terminal.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct SetInputMode(pub InputSettings);
 
 impl EscCode for SetInputMode {
-    const OPCODE: u16 = 0x80;
-    fn args(&self) -> Vec<String> {
-        encode_args![self.0]
+    fn opcode(&self) -> u16 { 0x80 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => self.0]
     }
 }
 
 /// In local echo mode, do not process non-input commands until input has been sent.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct HoldForInput;
 
 impl EscCode for HoldForInput {
-    const OPCODE: u16 = 0x87;
+    fn opcode(&self) -> u16 { 0x87 }
 }
@@ -1,64 +1,114 @@
+use std::io::{self, Write};
+
+use encode::{self, Counter};
+
+/// A command which can be encoded as a notty escape sequence.
+///
+/// This trait is object safe, so that commands of different types can be stored together as
+/// `Box<dyn EscCode>`. The `Command` enum is an alternative which can also be decoded into.
 pub trait EscCode {
 
-    const OPCODE: u16;
+    /// The opcode identifying this command.
+    fn opcode(&self) -> u16;
+
+    /// Returns true if this command does nothing, in which case it is not encoded at all.
+    fn is_noop(&self) -> bool {
+        false
+    }
+
+    /// Write the arguments of this command, each preceded by `;`.
+    fn write_args(&self, _w: &mut dyn io::Write) -> io::Result<()> {
+        Ok(())
+    }
 
-    fn args(&self) -> Vec<String> {
-        Vec::new()
+    /// Pass the unencoded contents of each attachment of this command to a callback, in order.
+    fn each_attachment(&self, _f: &mut dyn FnMut(&[u8]) -> io::Result<()>) -> io::Result<()> {
+        Ok(())
     }
 
-    fn attachments(&self) -> Vec<Vec<u8>> {
-        Vec::new()
+    /// Write the body of the escape sequence for this command: everything between the
+    /// introducer and the string terminator.
+    fn write_body(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        write!(w, "{:x}", self.opcode())?;
+        self.write_args(w)?;
+        self.each_attachment(&mut |attachment| {
+            w.write_all(b"#")?;
+            encode::write_base64(w, attachment)
+        })
     }
 
+    /// The length in bytes of the body of the escape sequence for this command.
+    fn body_len(&self) -> usize {
+        let mut counter = Counter::new();
+        let _ = write!(counter, "{:x}", self.opcode());
+        let _ = self.write_args(&mut counter);
+        let mut len = counter.count();
+        let _ = self.each_attachment(&mut |attachment| {
+            len += 1 + encode::base64_len(attachment.len());
+            Ok(())
+        });
+        len
+    }
+
+    /// Write this command as an escape sequence, without any intermediate allocations.
+    fn encode_to(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        Encoder::new().encode_to(w, self)
+    }
+
+    /// The length in bytes of the escape sequence for this command.
+    fn encoded_len(&self) -> usize {
+        Encoder::new().encoded_len(self)
+    }
+
+    /// Encode this command as an escape sequence, introduced by `ESC _ [` and terminated by the
+    /// C1 string terminator. Use an `Encoder` to frame the sequence differently.
     fn encode(&self) -> String {
-        let mut string = format!("\x1b_[{:x}", Self::OPCODE);
-        for arg in self.args() {
-            string.push(';');
-            string.push_str(&arg);
-        }
-        for attachment in self.attachments() {
-            string.push('#');
-            string.push_str(unsafe {
-                &String::from_utf8_unchecked(::base64::u8en(&attachment).unwrap())
-            });
-        }
-        string + "\u{9c}"
+        let mut buf = Vec::with_capacity(self.encoded_len());
+        let _ = self.encode_to(&mut buf);
+        String::from_utf8(buf).unwrap_or_default()
     }
 
 }
 
 macro_rules! encode_args {
-    (? $arg:expr, $($rest:tt)*) => {{
-        encode_args!(Vec::<String>::new() => ? $arg, $($rest)*)
-    }};
-    (? $arg:expr) => {{
-        encode_args!(Vec::<String>::new() => ? $arg)
-    }};
-    ($arg:expr, $($rest:tt)*) => {{
-        encode_args!(Vec::<String>::new() => $arg, $($rest)*)
-    }};
-    ($arg:expr) => {{
-        encode_args!(Vec::<String>::new() => $arg)
-    }};
-    ($vec:expr => ? $arg:expr, $($rest:tt)*) => {{
-        $arg.map(|arg| $vec.push(arg.encode()));
-        encode_args!($vec => $($rest)*)
-    }};
-    ($vec:expr => ? $arg:expr) => {{
-        $arg.map(|arg| $vec.push(arg.encode()));
-        $vec
+    ($w:ident =>) => {
+        Ok(())
+    };
+    ($w:ident => .. $args:expr, $($rest:tt)*) => {{
+        for arg in &$args {
+            $w.write_all(b";")?;
+            arg.encode_to($w)?;
+        }
+        encode_args!($w => $($rest)*)
     }};
-    ($vec:expr => $arg:expr, $($rest:tt)*) => {{
-        $vec.push($arg.encode());
-        encode_args!($vec => $($rest)*)
+    ($w:ident => .. $args:expr) => {
+        encode_args!($w => .. $args,)
+    };
+    ($w:ident => ? $arg:expr, $($rest:tt)*) => {{
+        if let Some(ref arg) = $arg {
+            $w.write_all(b";")?;
+            arg.encode_to($w)?;
+        }
+        encode_args!($w => $($rest)*)
     }};
-    ($vec:expr => $arg:expr) => {{
-        $vec.push($arg.encode());
-        $vec
+    ($w:ident => ? $arg:expr) => {
+        encode_args!($w => ? $arg,)
+    };
+    ($w:ident => $arg:expr, $($rest:tt)*) => {{
+        $w.write_all(b";")?;
+        $arg.encode_to($w)?;
+        encode_args!($w => $($rest)*)
     }};
+    ($w:ident => $arg:expr) => {
+        encode_args!($w => $arg,)
+    };
 }
 
+mod command;
+mod decoder;
+mod encoder;
 mod erase;
+mod link;
 mod meta;
 mod movement;
 mod panel;
@@ -66,12 +116,16 @@ mod put;
 mod style;
 mod tooltip;
 
+pub use self::command::Command;
+pub use self::decoder::{Decoder, decode, decode_body, try_decode, try_decode_body};
+pub use self::encoder::{Control, Encoder, Passthrough};
 pub use self::erase::{Erase, RemoveChars, RemoveRows, InsertBlank, InsertRows};
+pub use self::link::{BeginHyperlink, EndHyperlink, AddHyperlink};
 pub use self::meta::{SetTitle, SetInputMode, HoldForInput};
 pub use self::panel::{PushPanel, PopPanel, SplitPanel, UnsplitPanel, AdjustPanelSplit,
                       RotateSectionDown, RotateSectionUp, SwitchActiveSection};
 pub use self::movement::{Move, ScrollScreen};
 pub use self::put::{PutMedia, PutMediaAt};
 pub use self::style::{SetTextStyle, DefaultTextStyle, SetCursorStyle, DefaultCursorStyle,
-                      SetStyleInArea, DefaultStyleInArea};
+                      SetStyleInArea, DefaultStyleInArea, diff_text_styles};
 pub use self::tooltip::{AddToolTip, AddDropDown, RemoveToolTip};
@@ -1,8 +1,10 @@
+use std::io;
+
 use args::{Argument, Direction, Movement};
 use cmds::EscCode;
 
 /// Move the cursor.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct Move {
     pub movement: Movement,
 }
@@ -14,13 +16,14 @@ impl Move {
 }
 
 impl EscCode for Move {
-    const OPCODE: u16 = 0x18;
-    fn args(&self) -> Vec<String> {
-        encode_args![self.movement]
+    fn opcode(&self) -> u16 { 0x18 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => self.movement]
     }
 }
 
 /// Scroll the screen.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct ScrollScreen {
     pub dir: Direction,
     pub n: u32,
@@ -36,8 +39,8 @@ impl ScrollScreen {
 }
 
 impl EscCode for ScrollScreen {
-    const OPCODE: u16 = 0x19;
-    fn args(&self) -> Vec<String> {
-        encode_args![self.dir, self.n]
+    fn opcode(&self) -> u16 { 0x19 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => self.dir, self.n]
     }
 }
@@ -1,27 +1,31 @@
+use std::io;
+
 use args::{Argument, SplitKind, SaveGrid, ResizeRule};
 use cmds::EscCode;
 
 /// Add a new panel to the top of a section of the screen. This panel will contain an empty grid.
 /// Argument 1: Which section to put the panel over (defaults to the active section).
 /// Argument 2: Whether or not the new grid created retains offscreen state (defaults to true).
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct PushPanel(pub Option<u64>, pub Option<bool>);
 
 impl EscCode for PushPanel {
-    const OPCODE: u16 = 0x60;
-    fn args(&self) -> Vec<String> {
-        encode_args![?self.0, ?self.1]
+    fn opcode(&self) -> u16 { 0x60 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => ?self.0, ?self.1]
     }
 }
 
 /// Remove the top panel in a section of the screen. If that section has only one panel, this does
 /// nothing.
 /// Argument 1: Which section to remove the panel from (defaults to the active section).
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct PopPanel(pub Option<u64>);
 
 impl EscCode for PopPanel {
-    const OPCODE: u16 = 0x61;
-    fn args(&self) -> Vec<String> {
-        encode_args![?self.0]
+    fn opcode(&self) -> u16 { 0x61 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => ?self.0]
     }
 }
 
@@ -31,10 +35,11 @@ impl EscCode for PopPanel {
 /// * kind: The axis and position of the split in this panel.
 /// * save: Which of the subsections the current contents of the panel should be saved to.
 /// * rule: What rules should be applied to resizing the current contents to fit into the
-/// subsection.
+///   subsection.
 /// * split_tag: Which section to split (defaults to the active section).
 /// * retain_offscreen_state: Whether new panels created by this split retain offscreen state
-/// (defaults to true)
+///   (defaults to true)
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct SplitPanel {
     pub l_tag: u64,
     pub r_tag: u64,
@@ -66,16 +71,17 @@ impl SplitPanel {
 }
 
 impl EscCode for SplitPanel {
-    const OPCODE: u16 = 0x62;
-    fn args(&self) -> Vec<String> {
-        encode_args![self.l_tag, self.r_tag, self.kind, ?self.save, ?self.rule,
-                     ?self.split_tag, ?self.retain_offscreen_state]
+    fn opcode(&self) -> u16 { 0x62 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => self.l_tag, self.r_tag, self.kind, ?self.save, ?self.rule,
+                          ?self.split_tag, ?self.retain_offscreen_state]
     }
 }
 
 /// Remove the split from a split panel.
 /// * save: Which side of the split to save into the unsplit section.
 /// * unsplit_tag: Which section's panel to unsplit 
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct UnsplitPanel {
     pub unsplit_tag: u64,
     pub save: SaveGrid,
@@ -91,9 +97,9 @@ impl UnsplitPanel {
 }
 
 impl EscCode for UnsplitPanel {
-    const OPCODE: u16 = 0x63;
-    fn args(&self) -> Vec<String> {
-        encode_args![self.save, self.unsplit_tag]
+    fn opcode(&self) -> u16 { 0x63 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => self.save, self.unsplit_tag]
     }
 }
 
@@ -101,6 +107,7 @@ impl EscCode for UnsplitPanel {
 /// * kind: The new axis and position for the split.
 /// * rule: How to resize child panels.
 /// * adjust_tag: Which section's panel to adjust the split of.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct AdjustPanelSplit {
     pub kind: SplitKind,
     pub rule: ResizeRule,
@@ -114,42 +121,45 @@ impl AdjustPanelSplit {
 }
 
 impl EscCode for AdjustPanelSplit {
-    const OPCODE: u16 = 0x64;
-    fn args(&self) -> Vec<String> {
-        encode_args![self.kind, self.rule, self.adjust_tag]
+    fn opcode(&self) -> u16 { 0x64 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => self.kind, self.rule, self.adjust_tag]
     }
 }
 
 /// Rotate the stack of a section down, putting the top panel on the bottom of the stack.
 /// Argument: the tag of the section being rotated (defaults to active section).
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct RotateSectionDown(pub Option<u64>);
 
 impl EscCode for RotateSectionDown {
-    const OPCODE: u16 = 0x65;
-    fn args(&self) -> Vec<String> {
-        encode_args![?self.0]
+    fn opcode(&self) -> u16 { 0x65 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => ?self.0]
     }
 }
 
 /// Rotate the stack of a section up, putting the bottom panel on the top of the stack.
 /// Argument: the tag of the section being rotated (defaults to active section).
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct RotateSectionUp(pub Option<u64>);
 
 impl EscCode for RotateSectionUp {
-    const OPCODE: u16 = 0x66;
-    fn args(&self) -> Vec<String> {
-        encode_args![?self.0]
+    fn opcode(&self) -> u16 { 0x66 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => ?self.0]
     }
 }
 
 /// Switch which section of the screen is currently active. The active section must have a grid
 /// panel at the top of it.
 /// Argument: The tag of the section to switch to.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct SwitchActiveSection(pub u64);
 
 impl EscCode for SwitchActiveSection {
-    const OPCODE: u16 = 0x67;
-    fn args(&self) -> Vec<String> {
-        encode_args![self.0]
+    fn opcode(&self) -> u16 { 0x67 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => self.0]
     }
 }
@@ -1,14 +1,17 @@
+use std::io;
+
 use args::{Argument, Coords, MediaPosition};
 use cmds::EscCode;
 use MediaFormat;
 
 /// Command to put some media object at the cursor position.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct PutMedia {
-    width: u32,
-    height: u32,
-    position: MediaPosition,
-    fmt: MediaFormat,
-    data: Vec<u8>
+    pub width: u32,
+    pub height: u32,
+    pub position: MediaPosition,
+    pub fmt: MediaFormat,
+    pub data: Vec<u8>
 }
 
 impl PutMedia {
@@ -24,23 +27,25 @@ impl PutMedia {
 }
 
 impl EscCode for PutMedia {
-    const OPCODE: u16 = 0x14;
-    fn args(&self) -> Vec<String> {
-        encode_args![self.width, self.height, self.position]
+    fn opcode(&self) -> u16 { 0x14 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => self.width, self.height, self.position]
     }
-    fn attachments(&self) -> Vec<Vec<u8>> {
-        vec![Vec::from(self.fmt.mime().as_bytes()), self.data.clone()]
+    fn each_attachment(&self, f: &mut dyn FnMut(&[u8]) -> io::Result<()>) -> io::Result<()> {
+        f(self.fmt.mime().as_bytes())?;
+        f(&self.data)
     }
 }
 
 /// Command to put some media object at a give coordinate.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct PutMediaAt {
-    coords: Coords,
-    width: u32,
-    height: u32,
-    position: MediaPosition,
-    fmt: MediaFormat,
-    data: Vec<u8>
+    pub coords: Coords,
+    pub width: u32,
+    pub height: u32,
+    pub position: MediaPosition,
+    pub fmt: MediaFormat,
+    pub data: Vec<u8>
 }
 
 impl PutMediaAt {
@@ -56,11 +61,12 @@ impl PutMediaAt {
     }
 }
 impl EscCode for PutMediaAt {
-    const OPCODE: u16 = 0x15;
-    fn args(&self) -> Vec<String> {
-        encode_args![self.coords, self.width, self.height, self.position]
+    fn opcode(&self) -> u16 { 0x15 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => self.coords, self.width, self.height, self.position]
     }
-    fn attachments(&self) -> Vec<Vec<u8>> {
-        vec![Vec::from(self.fmt.mime().as_bytes()), self.data.clone()]
+    fn each_attachment(&self, f: &mut dyn FnMut(&[u8]) -> io::Result<()>) -> io::Result<()> {
+        f(self.fmt.mime().as_bytes())?;
+        f(&self.data)
     }
 }
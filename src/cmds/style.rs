@@ -1,56 +1,172 @@
-use args::{Argument, Area, Style};
-use cmds::EscCode;
+use std::io;
 
-/// Set a style of all content written from now on.
-pub struct SetTextStyle(pub Style);
+use args::{Argument, Area, Style, TextStyle};
+use cmds::{Command, EscCode};
+
+/// Set styles of all content written from now on, in order.
+///
+/// A command without any styles does nothing, and is not encoded at all; otherwise it would be
+/// encoded the same way as `DefaultTextStyle`. A single style is encoded the same way as it was
+/// before styles could be set together.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SetTextStyle(pub Vec<Style>);
 
 impl EscCode for SetTextStyle {
-    const OPCODE: u16 = 0x30;
-    fn args(&self) -> Vec<String> {
-        encode_args![self.0]
+    fn opcode(&self) -> u16 { 0x30 }
+    fn is_noop(&self) -> bool { self.0.is_empty() }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => ..self.0]
     }
 }
 
 /// Unset all styles that have been sent for content to be written.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct DefaultTextStyle;
 
 impl EscCode for DefaultTextStyle {
-    const OPCODE: u16 = 0x30;
+    fn opcode(&self) -> u16 { 0x30 }
 }
 
-/// Set the style of the cursor marker in the terminal.
-pub struct SetCursorStyle(pub Style);
+/// Set styles of the cursor marker in the terminal, in order. Like `SetTextStyle`, a command
+/// without any styles is not encoded.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SetCursorStyle(pub Vec<Style>);
 
 impl EscCode for SetCursorStyle {
-    const OPCODE: u16 = 0x31;
-    fn args(&self) -> Vec<String> {
-        encode_args![self.0]
+    fn opcode(&self) -> u16 { 0x31 }
+    fn is_noop(&self) -> bool { self.0.is_empty() }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => ..self.0]
     }
 }
 
 /// Unset all styles on the cursor and use the default cursor style.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct DefaultCursorStyle;
 
 impl EscCode for DefaultCursorStyle {
-    const OPCODE: u16 = 0x31;
+    fn opcode(&self) -> u16 { 0x31 }
 }
 
-/// Set a style in a given area of the grid.
-pub struct SetStyleInArea(pub Area, pub Style);
+/// Set styles in a given area of the grid, in order. Like `SetTextStyle`, a command without any
+/// styles is not encoded; it would be encoded the same way as `DefaultStyleInArea`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SetStyleInArea(pub Area, pub Vec<Style>);
 
 impl EscCode for SetStyleInArea {
-    const OPCODE: u16 = 0x32;
-    fn args(&self) -> Vec<String> {
-        encode_args![self.0, self.1]
+    fn opcode(&self) -> u16 { 0x32 }
+    fn is_noop(&self) -> bool { self.1.is_empty() }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => self.0, ..self.1]
     }
 }
 
 /// Unset all styles in a given area of the grid.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct DefaultStyleInArea(pub Area);
 
 impl EscCode for DefaultStyleInArea {
-    const OPCODE: u16 = 0x32;
-    fn args(&self) -> Vec<String> {
-        encode_args![self.0]
+    fn opcode(&self) -> u16 { 0x32 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => self.0]
+    }
+}
+
+/// The shortest sequence of `SetTextStyle` and `DefaultTextStyle` commands which changes the text
+/// style from one style to another.
+///
+/// The styles which differ are set together, unless the escape sequences are shorter when every
+/// style is reset to its default and then the styles of the new style which are not the default
+/// are set.
+pub fn diff_text_styles(from: &TextStyle, to: &TextStyle) -> Vec<Command> {
+    let set = |styles: Vec<Style>| {
+        if styles.is_empty() { None } else { Some(Command::from(SetTextStyle(styles))) }
+    };
+    let changes: Vec<Command> = set(from.changes(to)).into_iter().collect();
+    let reset: Vec<Command> = Some(Command::from(DefaultTextStyle)).into_iter()
+        .chain(set(TextStyle::default().changes(to))).collect();
+    let len = |cmds: &[Command]| cmds.iter().map(|cmd| cmd.encoded_len()).sum::<usize>();
+    if len(&reset) < len(&changes) { reset } else { changes }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use args::{Color, ConfigStyle, CodeGroup, Style, TextStyle};
+    use cmds::{Command, DefaultTextStyle, EscCode, SetTextStyle};
+
+    use super::diff_text_styles;
+
+    fn style(styles: &[Style]) -> TextStyle {
+        let mut text_style = TextStyle::default();
+        for &style in styles {
+            text_style.apply(style);
+        }
+        text_style
+    }
+
+    #[test]
+    fn applying_styles() {
+        let text_style = style(&[Style::Underline(2), Style::Bold(true),
+                                 Style::FgColor(Color::Palette(3)), Style::Bold(false),
+                                 Style::Configured(ConfigStyle::CodeGroup(CodeGroup::Macro))]);
+        assert_eq!(text_style, TextStyle {
+            underline: 2,
+            fg: Color::Palette(3),
+            configured: ConfigStyle::CodeGroup(CodeGroup::Macro),
+            ..TextStyle::default()
+        });
+        assert_eq!(TextStyle::default().changes(&text_style),
+                   [Style::Underline(2), Style::FgColor(Color::Palette(3)),
+                    Style::Configured(ConfigStyle::CodeGroup(CodeGroup::Macro))]);
+        assert_eq!(text_style.changes(&text_style), []);
+    }
+
+    #[test]
+    fn diffing_styles() {
+        let set = |styles: &[Style]| -> Command { SetTextStyle(styles.to_vec()).into() };
+        let bold = style(&[Style::Bold(true)]);
+        let busy = style(&[Style::Bold(true), Style::Italic(true), Style::Blink(true),
+                           Style::Opacity(0x80), Style::BgColor(Color::True(1, 2, 3))]);
+
+        assert_eq!(diff_text_styles(&bold, &bold), []);
+        assert_eq!(diff_text_styles(&TextStyle::default(), &bold), [set(&[Style::Bold(true)])]);
+        // Resetting is shorter than unsetting a single style.
+        assert_eq!(diff_text_styles(&bold, &TextStyle::default()), [DefaultTextStyle.into()]);
+        assert_eq!(diff_text_styles(&bold, &busy),
+                   [set(&[Style::Italic(true), Style::Blink(true), Style::Opacity(0x80),
+                          Style::BgColor(Color::True(1, 2, 3))])]);
+
+        // Resetting is shorter than unsetting four styles and setting another.
+        let underlined = style(&[Style::Underline(1)]);
+        assert_eq!(diff_text_styles(&busy, &underlined),
+                   [DefaultTextStyle.into(), set(&[Style::Underline(1)])]);
+        assert_eq!(diff_text_styles(&busy, &TextStyle::default()), [DefaultTextStyle.into()]);
+
+        // Four unsets are shorter than resetting and setting a long color, although they are more
+        // styles.
+        let white = Style::FgColor(Color::True(0xff, 0xff, 0xff));
+        let flashy = style(&[Style::Bold(true), Style::Italic(true), Style::Blink(true),
+                             Style::InvertColors(true), white]);
+        let unset = [Style::Bold(false), Style::Italic(false), Style::Blink(false),
+                     Style::InvertColors(false)];
+        assert_eq!(diff_text_styles(&flashy, &style(&[white])), [set(&unset)]);
+        assert_eq!(set(&unset).encoded_len(), 23);
+        assert_eq!(Command::from(DefaultTextStyle).encoded_len() + set(&[white]).encoded_len(), 27);
+
+        // Applying the commands always gives the style which was diffed to.
+        for &(from, to) in &[(bold, busy), (busy, underlined), (underlined, bold)] {
+            let mut style = from;
+            for cmd in diff_text_styles(&from, &to) {
+                match cmd {
+                    Command::SetTextStyle(SetTextStyle(styles)) => {
+                        styles.into_iter().for_each(|s| style.apply(s))
+                    }
+                    Command::DefaultTextStyle(_)                => style = TextStyle::default(),
+                    _                                           => unreachable!(),
+                }
+            }
+            assert_eq!(style, to);
+        }
     }
 }
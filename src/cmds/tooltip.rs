@@ -1,41 +1,55 @@
+use std::io;
+
 use args::{Argument, Coords};
 use cmds::EscCode;
 
 /// Add a tooltip at a given coordinate.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct AddToolTip(pub Coords, pub String);
 
 impl EscCode for AddToolTip {
-    const OPCODE: u16 = 0x50;
-    fn args(&self) -> Vec<String> {
-        encode_args![self.0]
+    fn opcode(&self) -> u16 { 0x50 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => self.0]
     }
-    fn attachments(&self) -> Vec<Vec<u8>> {
-        vec![self.1.clone().into_bytes()]
+    fn each_attachment(&self, f: &mut dyn FnMut(&[u8]) -> io::Result<()>) -> io::Result<()> {
+        f(self.1.as_bytes())
     }
 }
 
 /// Add a dropdown menu at a given coordinate.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct AddDropDown {
-    coords: Coords,
-    options: Vec<String>,
+    pub coords: Coords,
+    pub options: Vec<String>,
+}
+
+impl AddDropDown {
+    pub fn new(coords: Coords, options: Vec<String>) -> AddDropDown {
+        AddDropDown { coords: coords, options: options }
+    }
 }
 
 impl EscCode for AddDropDown {
-    const OPCODE: u16 = 0x51;
-    fn args(&self) -> Vec<String> {
-        encode_args![self.coords]
+    fn opcode(&self) -> u16 { 0x51 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => self.coords]
     }
-    fn attachments(&self) -> Vec<Vec<u8>> {
-        self.options.iter().cloned().map(String::into_bytes).collect()
+    fn each_attachment(&self, f: &mut dyn FnMut(&[u8]) -> io::Result<()>) -> io::Result<()> {
+        for option in &self.options {
+            f(option.as_bytes())?;
+        }
+        Ok(())
     }
 }
 
 /// Remove a tooltip or dropdown from a given coordinate, if there is one there.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct RemoveToolTip(pub Coords);
 
 impl EscCode for RemoveToolTip {
-    const OPCODE: u16 = 0x54;
-    fn args(&self) -> Vec<String> {
-        encode_args![self.0]
+    fn opcode(&self) -> u16 { 0x54 }
+    fn write_args(&self, w: &mut dyn io::Write) -> io::Result<()> {
+        encode_args![w => self.0]
     }
 }
@@ -0,0 +1,75 @@
+//! Helpers for writing escape sequences directly into a writer.
+
+use std::io;
+
+const BASE64: &'static [u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A writer which discards its input, counting the number of bytes written to it.
+pub struct Counter(usize);
+
+impl Counter {
+    pub fn new() -> Counter {
+        Counter(0)
+    }
+
+    pub fn count(&self) -> usize {
+        self.0
+    }
+}
+
+impl io::Write for Counter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Write the padded base64 encoding of some bytes, buffering the output on the stack.
+pub fn write_base64<W>(w: &mut W, bytes: &[u8]) -> io::Result<()>
+where W: io::Write + ?Sized {
+    let mut buf = [0; 256];
+    for chunk in bytes.chunks(buf.len() / 4 * 3) {
+        let mut len = 0;
+        for group in chunk.chunks(3) {
+            let b0 = group[0];
+            let b1 = group.get(1).cloned().unwrap_or(0);
+            let b2 = group.get(2).cloned().unwrap_or(0);
+            buf[len] = BASE64[(b0 >> 2) as usize];
+            buf[len + 1] = BASE64[((b0 << 4 | b1 >> 4) & 0x3f) as usize];
+            buf[len + 2] = if group.len() > 1 { BASE64[((b1 << 2 | b2 >> 6) & 0x3f) as usize] }
+                           else { b'=' };
+            buf[len + 3] = if group.len() > 2 { BASE64[(b2 & 0x3f) as usize] } else { b'=' };
+            len += 4;
+        }
+        w.write_all(&buf[..len])?;
+    }
+    Ok(())
+}
+
+/// The length of the padded base64 encoding of `len` bytes.
+pub fn base64_len(len: usize) -> usize {
+    len.div_ceil(3) * 4
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn base64_matches_base64_crate() {
+        let bytes: Vec<u8> = (0..1000).map(|n| (n * 37 % 256) as u8).collect();
+        for len in (0..12).chain(190..194).chain(990..1000) {
+            let mut buf = Vec::new();
+            write_base64(&mut buf, &bytes[..len]).unwrap();
+            assert_eq!(buf, ::base64::u8en(&bytes[..len]).unwrap(), "length {}", len);
+            assert_eq!(buf.len(), base64_len(len));
+        }
+    }
+
+}
@@ -0,0 +1,159 @@
+use std::error::Error;
+use std::fmt;
+
+/// The reason an escape sequence or one of its arguments failed to decode.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ErrorKind {
+    /// The sequence did not begin with the notty introducer and end with a string terminator.
+    Framing,
+    /// The opcode is not that of any known command.
+    UnknownOpcode,
+    /// An argument began with a tag which does not identify any variant of its type.
+    UnknownTag,
+    /// A required argument, field or attachment was absent.
+    Missing,
+    /// A field was not a hexadecimal number.
+    InvalidDigit,
+    /// A value was outside of the range of values allowed for its field.
+    OutOfRange,
+    /// A value was too large to fit into the field it was decoded into.
+    Overflow,
+    /// An argument contained more fields than its type uses.
+    TrailingFields,
+    /// An attachment was not valid base64, or did not have the expected contents.
+    InvalidAttachment,
+    /// A sequence exceeded one of the limits set on the decoder.
+    LimitExceeded,
+}
+
+impl ErrorKind {
+    fn description(&self) -> &'static str {
+        match *self {
+            ErrorKind::Framing              => "invalid escape sequence framing",
+            ErrorKind::UnknownOpcode        => "unknown opcode",
+            ErrorKind::UnknownTag           => "unknown tag",
+            ErrorKind::Missing              => "missing value",
+            ErrorKind::InvalidDigit         => "invalid hexadecimal digit",
+            ErrorKind::OutOfRange           => "value out of range",
+            ErrorKind::Overflow             => "value too large",
+            ErrorKind::TrailingFields       => "unexpected trailing fields",
+            ErrorKind::InvalidAttachment    => "invalid attachment",
+            ErrorKind::LimitExceeded        => "decoder limit exceeded",
+        }
+    }
+}
+
+/// An error encountered decoding an escape sequence or argument.
+///
+/// Errors from decoding a single argument have no opcode or argument index; these are filled in
+/// when the argument is decoded as part of a command.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct DecodeError {
+    pub kind: ErrorKind,
+    /// The opcode of the command being decoded.
+    pub opcode: Option<u16>,
+    /// The (zero-based) index of the argument being decoded.
+    pub arg: Option<usize>,
+    /// The token which caused the error, if there was one.
+    pub token: Option<String>,
+    /// The name of the field being decoded, if it is known.
+    pub field: Option<&'static str>,
+}
+
+impl DecodeError {
+    pub fn new(kind: ErrorKind) -> DecodeError {
+        DecodeError {
+            kind: kind,
+            opcode: None,
+            arg: None,
+            token: None,
+            field: None,
+        }
+    }
+
+    /// Set the offending token.
+    pub fn with_token(self, token: &str) -> DecodeError {
+        DecodeError { token: Some(String::from(token)), ..self }
+    }
+
+    /// Set the name of the field in which this error occurred.
+    pub fn in_field(self, field: &'static str) -> DecodeError {
+        DecodeError { field: Some(field), ..self }
+    }
+
+    /// Set the command and argument in which this error occurred.
+    pub fn in_arg(self, opcode: u16, arg: usize) -> DecodeError {
+        DecodeError { opcode: Some(opcode), arg: Some(arg), ..self }
+    }
+
+    /// Set the command in which this error occurred.
+    pub fn in_command(self, opcode: u16) -> DecodeError {
+        DecodeError { opcode: Some(opcode), ..self }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.kind.description())?;
+        if let Some(ref token) = self.token {
+            write!(f, " {:?}", token)?;
+        }
+        if let Some(field) = self.field {
+            write!(f, " in field {}", field)?;
+        }
+        if let Some(arg) = self.arg {
+            write!(f, " in argument {}", arg)?;
+        }
+        if let Some(opcode) = self.opcode {
+            write!(f, " of command {:#x}", opcode)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for DecodeError { }
+
+/// The reason a panel command could not be applied to a layout.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum LayoutError {
+    /// No section has this tag.
+    UnknownSection(u64),
+    /// A new section was given a tag which is already in use.
+    TagInUse(u64),
+    /// The top panel of this section is not a grid, or the section is not on screen.
+    NotAGrid(u64),
+    /// The top panel of this section is not a split.
+    NotASplit(u64),
+    /// This section is too small to be split as requested.
+    TooSmall(u64),
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LayoutError::UnknownSection(tag)    => write!(f, "no section is tagged {:#x}", tag),
+            LayoutError::TagInUse(tag)          => write!(f, "tag {:#x} is already in use", tag),
+            LayoutError::NotAGrid(tag)          => {
+                write!(f, "section {:#x} does not show a grid", tag)
+            }
+            LayoutError::NotASplit(tag)         => write!(f, "section {:#x} is not split", tag),
+            LayoutError::TooSmall(tag)          => {
+                write!(f, "section {:#x} is too small to split", tag)
+            }
+        }
+    }
+}
+
+impl Error for LayoutError { }
+
+/// A string which does not name a color. Holds the string.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct ParseColorError(pub String);
+
+impl fmt::Display for ParseColorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "not a color: {:?}", self.0)
+    }
+}
+
+impl Error for ParseColorError { }
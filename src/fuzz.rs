@@ -0,0 +1,186 @@
+//! Randomized tests checking that decoding, and applying what was decoded, never panics whatever
+//! the input.
+
+use ansi::Importer;
+use args::*;
+use cmds::*;
+use parser::{Event, Parser};
+use screen::Screen;
+use MediaFormat;
+
+const ROUNDS: usize = 20000;
+
+// Characters which are meaningful to the decoder are much more likely to be generated than
+// others, so that the generated input exercises the decoder deeply.
+const ALPHABET: &'static [u8] = b"0123456789abcdef.;;..##AZaz+/=\x1b_[]\\\xc2\x9c\x9f\xff\x00\x07";
+
+// A xorshift generator, so that failures are reproducible.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn bytes(&mut self) -> Vec<u8> {
+        let len = self.below(48);
+        (0..len).map(|_| ALPHABET[self.below(ALPHABET.len())]).collect()
+    }
+
+    // Generate bytes by mutating a valid encoding.
+    fn mutate(&mut self, seeds: &[String]) -> Vec<u8> {
+        let mut bytes = seeds[self.below(seeds.len())].clone().into_bytes();
+        for _ in 0..self.below(4) + 1 {
+            let idx = self.below(bytes.len() + 1);
+            let byte = ALPHABET[self.below(ALPHABET.len())];
+            match self.below(3) {
+                0 if idx < bytes.len()  => bytes[idx] = byte,
+                1 if idx < bytes.len()  => { bytes.remove(idx); }
+                _                       => bytes.insert(idx, byte),
+            }
+        }
+        bytes
+    }
+
+    fn input(&mut self, seeds: &[String]) -> Vec<u8> {
+        if self.below(2) == 0 { self.bytes() } else { self.mutate(seeds) }
+    }
+}
+
+fn seeds() -> Vec<String> {
+    vec![
+        Erase::new(Area::Bound(Region::new(0, 0, 4, 4))).encode(),
+        Erase::new(Area::CursorTo(Movement::Tab(Direction::Left, 2, true))).encode(),
+        Move::new(Movement::Position(Coords { x: 1, y: 2 })).encode(),
+        SetTextStyle(vec![Style::FgColor(Color::True(1, 2, 3))]).encode(),
+        SetStyleInArea(Area::Rows(1, 2), vec![Style::Underline(2), Style::Bold(true)]).encode(),
+        SetInputMode(InputSettings::ScreenEcho(EchoSettings {
+            lerase: 1, lnext: 2, werase: 3
+        })).encode(),
+        SplitPanel::new(1, 2, SplitKind::Vertical(3), Some(SaveGrid::Left),
+                        Some(ResizeRule::Percentage), Some(4), Some(false)).encode(),
+        PutMedia::new(1, 1, MediaPosition::Display(MediaAlignment::Center, MediaAlignment::Center),
+                      MediaFormat::Png, vec![1, 2, 3]).encode(),
+        AddDropDown::new(Coords { x: 0, y: 0 }, vec![String::from("a")]).encode(),
+        BeginHyperlink::new(Some(1), String::from("https://example.com")).encode(),
+        AddHyperlink(Area::CursorCell, String::from("a")).encode(),
+        ScrollScreen::new(Direction::Left, 3).encode(),
+        InsertRows::new(2, true).encode(),
+        RemoveChars::new(5).encode(),
+        PushPanel(Some(1), None).encode(),
+        UnsplitPanel::new(SaveGrid::Right, 0).encode(),
+        AdjustPanelSplit::new(SplitKind::Horizontal(1), ResizeRule::MaxRightBottom, 0).encode(),
+    ]
+}
+
+fn arguments(s: &str) {
+    macro_rules! decode_all {
+        ($($t:ty),*) => {$(
+            let _ = <$t>::decode(Some(s), None);
+            let _ = <$t>::try_decode(Some(s), None);
+        )*}
+    }
+    decode_all!(Area, bool, Color, CodeGroup, ConfigStyle, Coords, Direction, EchoSettings,
+                BufferSettings, InputSettings, MediaAlignment, MediaPosition, Movement, Region,
+                ResizeRule, SaveGrid, SplitKind, Style, u32, u64, i8, i64, Option<Coords>,
+                (u16, Direction), [Color; 3], Vec<Option<Style>>);
+}
+
+#[test]
+fn decoding_never_panics() {
+    let seeds = seeds();
+    let mut rng = Rng(0x2545f4914f6cdd1d);
+    let untrusted = Decoder::untrusted();
+    for _ in 0..ROUNDS {
+        let bytes = rng.input(&seeds);
+        let _ = untrusted.decode_bytes(&bytes);
+        let input = String::from_utf8_lossy(&bytes).into_owned();
+        let _ = decode(&input);
+        let _ = try_decode(&input);
+        let _ = untrusted.decode(&input);
+        let body = input.trim_start_matches("\x1b_[").trim_end_matches('\u{9c}');
+        let _ = decode_body(body);
+        let _ = untrusted.decode_body(body);
+        for arg in body.split(&[';', '#'][..]) {
+            arguments(arg);
+        }
+    }
+}
+
+#[test]
+fn parsing_never_panics() {
+    let seeds = seeds();
+    let mut rng = Rng(0x9e3779b97f4a7c15);
+    let decoder = Decoder { max_len: 32, ..Decoder::untrusted() };
+    let mut parser = Parser::with_decoder(decoder);
+    for _ in 0..ROUNDS {
+        let input = rng.input(&seeds);
+        let mut rest = &input[..];
+        while !rest.is_empty() {
+            let (chunk, tail) = rest.split_at(rng.below(rest.len()) + 1);
+            parser.feed(chunk, |event| if let Event::Malformed(bytes) = event {
+                assert!(bytes.len() <= decoder.max_len + 1);
+            });
+            rest = tail;
+        }
+        if rng.below(8) == 0 {
+            parser.finish(|_| ());
+        }
+    }
+}
+
+#[test]
+fn importing_never_panics() {
+    let seeds = seeds();
+    let mut rng = Rng(0xd1b54a32d192ed03);
+    let mut importer = Importer::new();
+    for _ in 0..ROUNDS {
+        let mut input = rng.input(&seeds);
+        for _ in 0..rng.below(3) {
+            input.extend_from_slice(b"\x1b[1;2m\x1b]2;");
+            input.extend(rng.bytes());
+        }
+        let mut rest = &input[..];
+        while !rest.is_empty() {
+            let (chunk, tail) = rest.split_at(rng.below(rest.len()) + 1);
+            importer.feed(chunk, |_| ());
+            rest = tail;
+        }
+        if rng.below(8) == 0 {
+            importer.finish(|_| ());
+        }
+    }
+}
+
+#[test]
+fn applying_never_panics() {
+    let seeds = seeds();
+    let mut rng = Rng(0xbf58476d1ce4e5b9);
+    let mut screen = Screen::new(8, 6);
+    for _ in 0..ROUNDS {
+        screen.feed(&rng.input(&seeds));
+        if rng.below(64) == 0 {
+            screen = Screen::new(rng.below(10) as u32, rng.below(10) as u32);
+        }
+    }
+}
+
+#[test]
+fn degenerate_regions_are_rejected() {
+    // A degenerate region is never widened to the whole screen, even when decoding leniently.
+    assert_eq!(Area::decode(Some("a.0.0.0.0"), None), None);
+    assert_eq!(Area::decode(Some("a.0.0.0.0"), Some(Area::CursorCell)), Some(Area::CursorCell));
+    assert!(Area::try_decode(Some("a.0.0.0.0"), None).is_err());
+    assert_eq!(Area::decode(Some("6.0.0.0.0"), None), None);
+    assert_eq!(Region::decode(Some("1.1.1.5"), None), None);
+    assert_eq!(decode("\x1b_[20;a.5.5.5.5\u{9c}"), None);
+    assert!(try_decode("\x1b_[20;a.5.5.5.5\u{9c}").is_err());
+    assert!(try_decode("\x1b_[20;6.5.5.5.5\u{9c}").is_err());
+}
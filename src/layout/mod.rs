@@ -0,0 +1,323 @@
+//! A model of how the screen is divided into sections and panels.
+//!
+//! The screen is a tree of tagged sections. Each section holds a stack of panels, of which only
+//! the top one is shown; a panel is either a grid or a split into two more sections. The root
+//! section is tagged 0 and covers the whole screen. `Layout` applies the panel commands to this
+//! tree, and tracks the region of the screen which each section covers.
+//!
+//! A `Viewport` tracks which part of some larger content is shown in a region, such as a grid
+//! which keeps its offscreen state.
+
+use args::{Region, ResizeRule, SaveGrid, SplitKind};
+use cmds::*;
+use LayoutError;
+
+mod section;
+mod viewport;
+
+pub use self::viewport::Viewport;
+
+use self::section::Section;
+
+/// The contents of a grid panel, which a layout creates and resizes as its sections change.
+///
+/// A layout which only tracks where its sections are can use `()`.
+pub trait Pane {
+    /// Create an empty grid to fill a region.
+    fn empty(region: Region) -> Self;
+    /// Fit the grid into a new region, following the rule for which of its contents to keep.
+    fn resize(&mut self, region: Region, rule: ResizeRule);
+}
+
+impl Pane for () {
+    fn empty(_: Region) { }
+    fn resize(&mut self, _: Region, _: ResizeRule) { }
+}
+
+/// The sections and panels of a screen, and which section is active.
+///
+/// The active section is always on screen with a grid at its top. When a command takes it off
+/// screen or covers its grid, the first section with a grid inside of the section the command
+/// changed becomes active instead. Commands which fail leave the layout unchanged.
+#[derive(Clone, Debug)]
+pub struct Layout<P = ()> {
+    root: Section<P>,
+    active: u64,
+}
+
+impl<P: Pane> Layout<P> {
+    /// Create a layout with a single section, tagged 0, which covers the region.
+    pub fn new(region: Region) -> Layout<P> {
+        Layout {
+            root: Section::new(0, region),
+            active: 0,
+        }
+    }
+
+    /// Apply one of the panel commands. Other commands are ignored.
+    pub fn apply(&mut self, cmd: &Command) -> Result<(), LayoutError> {
+        match *cmd {
+            Command::PushPanel(PushPanel(tag, _))                   => self.push(tag),
+            Command::PopPanel(PopPanel(tag))                        => self.pop(tag),
+            Command::SplitPanel(ref split)                          => {
+                let save = split.save.unwrap_or(SaveGrid::Left);
+                let rule = split.rule.unwrap_or(ResizeRule::MaxLeftTop);
+                self.split(split.split_tag, (split.l_tag, split.r_tag), split.kind, save, rule)
+            }
+            Command::UnsplitPanel(UnsplitPanel { unsplit_tag, save })   => {
+                self.unsplit(unsplit_tag, save)
+            }
+            Command::AdjustPanelSplit(AdjustPanelSplit { kind, rule, adjust_tag })  => {
+                self.adjust(adjust_tag, kind, rule)
+            }
+            Command::RotateSectionDown(RotateSectionDown(tag))      => self.rotate_down(tag),
+            Command::RotateSectionUp(RotateSectionUp(tag))          => self.rotate_up(tag),
+            Command::SwitchActiveSection(SwitchActiveSection(tag))  => self.switch(tag),
+            _                                                       => Ok(()),
+        }
+    }
+
+    /// Push an empty grid onto a section, which is the active section if no tag is given.
+    pub fn push(&mut self, tag: Option<u64>) -> Result<(), LayoutError> {
+        self.change(tag, Section::push)
+    }
+
+    /// Pop the top panel of a section, unless it is the only panel in that section.
+    pub fn pop(&mut self, tag: Option<u64>) -> Result<(), LayoutError> {
+        self.change(tag, Section::pop)
+    }
+
+    /// Move the top panel of a section to the bottom of its stack.
+    pub fn rotate_down(&mut self, tag: Option<u64>) -> Result<(), LayoutError> {
+        self.change(tag, Section::rotate_down)
+    }
+
+    /// Move the bottom panel of a section to the top of its stack.
+    pub fn rotate_up(&mut self, tag: Option<u64>) -> Result<(), LayoutError> {
+        self.change(tag, Section::rotate_up)
+    }
+
+    /// Split the grid at the top of a section into two new sections with the given tags, saving
+    /// the grid into one of them. If the active section is split, the side which the grid is
+    /// saved into becomes active.
+    pub fn split(&mut self, tag: Option<u64>, tags: (u64, u64), kind: SplitKind, save: SaveGrid,
+                 rule: ResizeRule) -> Result<(), LayoutError> {
+        for &new in &[tags.0, tags.1] {
+            if self.root.find(new).is_some() { return Err(LayoutError::TagInUse(new)) }
+        }
+        if tags.0 == tags.1 { return Err(LayoutError::TagInUse(tags.0)) }
+        let tag = tag.unwrap_or(self.active);
+        let was_active = self.active == tag;
+        self.change(Some(tag), |section| section.split(tags, kind, save, rule))?;
+        if was_active {
+            self.active = match save {
+                SaveGrid::Left  => tags.0,
+                SaveGrid::Right => tags.1,
+            };
+        }
+        Ok(())
+    }
+
+    /// Replace the split at the top of a section with the panels of one side of it. The
+    /// sections on the other side are removed.
+    pub fn unsplit(&mut self, tag: u64, save: SaveGrid) -> Result<(), LayoutError> {
+        self.change(Some(tag), |section| section.unsplit(save))
+    }
+
+    /// Move the split at the top of a section.
+    pub fn adjust(&mut self, tag: u64, kind: SplitKind, rule: ResizeRule)
+            -> Result<(), LayoutError> {
+        self.change(Some(tag), |section| section.adjust(kind, rule))
+    }
+
+    /// Make a section active. It must be on screen, with a grid at its top.
+    pub fn switch(&mut self, tag: u64) -> Result<(), LayoutError> {
+        let section = self.root.find(tag).ok_or(LayoutError::UnknownSection(tag))?;
+        if section.grid().is_none() || !self.root.shows(tag) {
+            return Err(LayoutError::NotAGrid(tag))
+        }
+        self.active = tag;
+        Ok(())
+    }
+
+    /// Move the whole layout into a new region, resizing every section.
+    pub fn resize(&mut self, region: Region, rule: ResizeRule) {
+        self.root.resize(region, rule);
+    }
+
+    /// The tag of the active section.
+    pub fn active(&self) -> u64 {
+        self.active
+    }
+
+    /// Returns true if a section has this tag, whether or not it is on screen.
+    pub fn contains(&self, tag: u64) -> bool {
+        self.root.find(tag).is_some()
+    }
+
+    /// The region of the screen covered by a section.
+    pub fn region(&self, tag: u64) -> Option<Region> {
+        self.root.find(tag).map(|section| section.region)
+    }
+
+    /// The grid at the top of a section, if its top panel is a grid.
+    pub fn grid(&self, tag: u64) -> Option<&P> {
+        self.root.find(tag).and_then(Section::grid)
+    }
+
+    pub fn grid_mut(&mut self, tag: u64) -> Option<&mut P> {
+        self.root.find_mut(tag).and_then(Section::grid_mut)
+    }
+
+    /// The grid at the top of the active section.
+    pub fn active_grid(&self) -> &P {
+        self.grid(self.active).expect("the active section always has a grid at its top")
+    }
+
+    pub fn active_grid_mut(&mut self) -> &mut P {
+        let active = self.active;
+        self.grid_mut(active).expect("the active section always has a grid at its top")
+    }
+
+    /// The tag and region of every grid on screen, from the left and top of the screen to the
+    /// right and bottom.
+    pub fn grids(&self) -> Vec<(u64, Region)> {
+        let mut grids = Vec::new();
+        self.root.grids(&mut grids);
+        grids
+    }
+
+    // Change a section, which is the active section if no tag is given, and then make sure the
+    // active section is still on screen with a grid at its top.
+    fn change<F>(&mut self, tag: Option<u64>, f: F) -> Result<(), LayoutError>
+    where F: FnOnce(&mut Section<P>) -> Result<(), LayoutError> {
+        let tag = tag.unwrap_or(self.active);
+        f(self.root.find_mut(tag).ok_or(LayoutError::UnknownSection(tag))?)?;
+        let active = self.active;
+        if !self.root.shows(active) || self.grid(active).is_none() {
+            self.active = match self.root.shows(tag) {
+                true    => self.root.find(tag).map_or(0, Section::first_grid),
+                false   => self.root.first_grid(),
+            };
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use args::*;
+    use cmds::*;
+    use LayoutError;
+
+    use super::Layout;
+
+    fn layout() -> Layout {
+        Layout::new(Region::new(0, 0, 10, 6))
+    }
+
+    fn split(layout: &mut Layout, tag: u64, tags: (u64, u64), kind: SplitKind)
+            -> Result<(), LayoutError> {
+        layout.split(Some(tag), tags, kind, SaveGrid::Left, ResizeRule::MaxLeftTop)
+    }
+
+    #[test]
+    fn splitting_sections() {
+        let mut layout = layout();
+        split(&mut layout, 0, (1, 2), SplitKind::Vertical(4)).unwrap();
+        split(&mut layout, 2, (3, 4), SplitKind::Horizontal(2)).unwrap();
+        assert_eq!(layout.active(), 1);
+        assert_eq!(layout.region(0), Some(Region::new(0, 0, 10, 6)));
+        assert_eq!(layout.grids(), [
+            (1, Region::new(0, 0, 4, 6)),
+            (3, Region::new(4, 0, 10, 2)),
+            (4, Region::new(4, 2, 10, 6)),
+        ]);
+        assert_eq!(layout.grid(0), None);
+        assert_eq!(layout.grid(3), Some(&()));
+
+        layout.adjust(0, SplitKind::Vertical(6), ResizeRule::MaxLeftTop).unwrap();
+        assert_eq!(layout.region(1), Some(Region::new(0, 0, 6, 6)));
+        assert_eq!(layout.region(4), Some(Region::new(6, 2, 10, 6)));
+
+        layout.unsplit(2, SaveGrid::Right).unwrap();
+        assert_eq!(layout.grids(), [(1, Region::new(0, 0, 6, 6)), (2, Region::new(6, 0, 10, 6))]);
+        assert!(!layout.contains(3));
+    }
+
+    #[test]
+    fn invalid_commands() {
+        let mut layout = layout();
+        split(&mut layout, 0, (1, 2), SplitKind::Horizontal(3)).unwrap();
+        assert_eq!(split(&mut layout, 1, (2, 3), SplitKind::Vertical(1)),
+                   Err(LayoutError::TagInUse(2)));
+        assert_eq!(split(&mut layout, 1, (5, 5), SplitKind::Vertical(1)),
+                   Err(LayoutError::TagInUse(5)));
+        assert_eq!(split(&mut layout, 7, (5, 6), SplitKind::Vertical(1)),
+                   Err(LayoutError::UnknownSection(7)));
+        assert_eq!(split(&mut layout, 0, (5, 6), SplitKind::Vertical(1)),
+                   Err(LayoutError::NotAGrid(0)));
+        assert_eq!(layout.unsplit(1, SaveGrid::Left), Err(LayoutError::NotASplit(1)));
+        assert_eq!(layout.switch(0), Err(LayoutError::NotAGrid(0)));
+        assert_eq!(layout.pop(Some(9)), Err(LayoutError::UnknownSection(9)));
+        assert_eq!(layout.grids(), [(1, Region::new(0, 0, 10, 3)), (2, Region::new(0, 3, 10, 6))]);
+
+        let mut layout: Layout = Layout::new(Region::new(0, 0, 10, 1));
+        assert_eq!(split(&mut layout, 0, (1, 2), SplitKind::Horizontal(0)),
+                   Err(LayoutError::TooSmall(0)));
+    }
+
+    #[test]
+    fn resizing_splits() {
+        let mut layout = layout();
+        layout.split(None, (1, 2), SplitKind::Vertical(40), SaveGrid::Left,
+                     ResizeRule::Percentage).unwrap();
+        layout.split(Some(2), (3, 4), SplitKind::Horizontal(2), SaveGrid::Left,
+                     ResizeRule::MaxRightBottom).unwrap();
+        assert_eq!(layout.grids(), [
+            (1, Region::new(0, 0, 4, 6)),
+            (3, Region::new(4, 0, 10, 4)),
+            (4, Region::new(4, 4, 10, 6)),
+        ]);
+        layout.resize(Region::new(0, 0, 20, 12), ResizeRule::MaxLeftTop);
+        assert_eq!(layout.grids(), [
+            (1, Region::new(0, 0, 8, 12)),
+            (3, Region::new(8, 0, 20, 10)),
+            (4, Region::new(8, 10, 20, 12)),
+        ]);
+        layout.resize(Region::new(0, 0, 5, 2), ResizeRule::MaxLeftTop);
+        assert_eq!(layout.grids(), [
+            (1, Region::new(0, 0, 2, 2)),
+            (3, Region::new(2, 0, 5, 1)),
+            (4, Region::new(2, 1, 5, 2)),
+        ]);
+    }
+
+    #[test]
+    fn active_section() {
+        let mut layout = layout();
+        layout.apply(&SplitPanel::new(1, 2, SplitKind::Vertical(5), Some(SaveGrid::Right), None,
+                                      None, None).into()).unwrap();
+        assert_eq!(layout.active(), 2);
+        layout.apply(&SwitchActiveSection(1).into()).unwrap();
+        assert_eq!(layout.active(), 1);
+
+        // Pushing a grid over the split takes the active section off screen.
+        layout.apply(&PushPanel(Some(0), None).into()).unwrap();
+        assert_eq!(layout.active(), 0);
+        assert_eq!(layout.switch(2), Err(LayoutError::NotAGrid(2)));
+        layout.apply(&RotateSectionDown(Some(0)).into()).unwrap();
+        assert_eq!(layout.grids().len(), 2);
+        layout.apply(&SwitchActiveSection(2).into()).unwrap();
+
+        layout.apply(&UnsplitPanel::new(SaveGrid::Left, 0).into()).unwrap();
+        assert_eq!(layout.active(), 0);
+        assert_eq!(layout.grids(), [(0, Region::new(0, 0, 10, 6))]);
+
+        // The pushed grid is still in the stack, beneath the grid saved from the split.
+        layout.apply(&PopPanel(None).into()).unwrap();
+        layout.apply(&PopPanel(None).into()).unwrap();
+        assert_eq!(layout.grids(), [(0, Region::new(0, 0, 10, 6))]);
+    }
+}
@@ -0,0 +1,233 @@
+use args::{Region, ResizeRule, SaveGrid, SplitKind};
+use LayoutError;
+
+use super::Pane;
+
+/// A tagged section of the screen, holding a stack of panels. Only the top panel is shown.
+#[derive(Clone, Debug)]
+pub struct Section<P> {
+    pub tag: u64,
+    pub region: Region,
+    // The stack of panels, with the top panel last. It is never empty.
+    pub panels: Vec<Panel<P>>,
+}
+
+#[derive(Clone, Debug)]
+pub enum Panel<P> {
+    Grid(P),
+    Split {
+        kind: SplitKind,
+        rule: ResizeRule,
+        children: Box<(Section<P>, Section<P>)>,
+    },
+}
+
+impl<P: Pane> Section<P> {
+    /// Create a section containing a single empty grid.
+    pub fn new(tag: u64, region: Region) -> Section<P> {
+        Section {
+            tag: tag,
+            region: region,
+            panels: vec![Panel::Grid(P::empty(region))],
+        }
+    }
+
+    /// Find the section with the given tag, among this section and every section inside of any
+    /// of its panels.
+    pub fn find(&self, tag: u64) -> Option<&Section<P>> {
+        if self.tag == tag { return Some(self) }
+        self.panels.iter().filter_map(|panel| match *panel {
+            Panel::Split { ref children, .. }   => {
+                children.0.find(tag).or_else(|| children.1.find(tag))
+            }
+            Panel::Grid(_)                      => None,
+        }).next()
+    }
+
+    pub fn find_mut(&mut self, tag: u64) -> Option<&mut Section<P>> {
+        if self.tag == tag { return Some(self) }
+        self.panels.iter_mut().filter_map(|panel| match *panel {
+            Panel::Split { ref mut children, .. }   => {
+                let (ref mut left, ref mut right) = **children;
+                match left.find_mut(tag) {
+                    Some(section)   => Some(section),
+                    None            => right.find_mut(tag),
+                }
+            }
+            Panel::Grid(_)                          => None,
+        }).next()
+    }
+
+    /// Returns true if the section with the given tag is on screen: it is this section, or it is
+    /// inside of the top panel of this section or of a section on screen.
+    pub fn shows(&self, tag: u64) -> bool {
+        self.tag == tag || match self.panels.last() {
+            Some(Panel::Split { children, .. }) => {
+                children.0.shows(tag) || children.1.shows(tag)
+            }
+            _                                   => false,
+        }
+    }
+
+    /// The grid at the top of this section, if its top panel is a grid.
+    pub fn grid(&self) -> Option<&P> {
+        match self.panels.last() {
+            Some(Panel::Grid(grid)) => Some(grid),
+            _                       => None,
+        }
+    }
+
+    pub fn grid_mut(&mut self) -> Option<&mut P> {
+        match self.panels.last_mut() {
+            Some(Panel::Grid(grid)) => Some(grid),
+            _                       => None,
+        }
+    }
+
+    /// The tag of the first section on screen which has a grid at its top, searching this
+    /// section and then the sections inside of its top panel.
+    pub fn first_grid(&self) -> u64 {
+        match self.panels.last() {
+            Some(Panel::Split { children, .. }) => children.0.first_grid(),
+            _                                   => self.tag,
+        }
+    }
+
+    /// Every section on screen which has a grid at its top, with its region, from the left and
+    /// top of the screen to the right and bottom.
+    pub fn grids(&self, grids: &mut Vec<(u64, Region)>) {
+        match self.panels.last() {
+            Some(Panel::Split { children, .. }) => {
+                children.0.grids(grids);
+                children.1.grids(grids);
+            }
+            _                                   => grids.push((self.tag, self.region)),
+        }
+    }
+
+    pub fn push(&mut self) -> Result<(), LayoutError> {
+        self.panels.push(Panel::Grid(P::empty(self.region)));
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Result<(), LayoutError> {
+        if self.panels.len() > 1 {
+            self.panels.pop();
+        }
+        Ok(())
+    }
+
+    pub fn rotate_down(&mut self) -> Result<(), LayoutError> {
+        if let Some(top) = self.panels.pop() {
+            self.panels.insert(0, top);
+        }
+        Ok(())
+    }
+
+    pub fn rotate_up(&mut self) -> Result<(), LayoutError> {
+        if !self.panels.is_empty() {
+            let bottom = self.panels.remove(0);
+            self.panels.push(bottom);
+        }
+        Ok(())
+    }
+
+    /// Split the grid at the top of this section into two sections, saving its contents into one
+    /// of them. Fails without changing anything if the top panel is not a grid, or the split
+    /// would leave either section empty.
+    pub fn split(&mut self, tags: (u64, u64), kind: SplitKind, save: SaveGrid, rule: ResizeRule)
+            -> Result<(), LayoutError> {
+        let (left_region, right_region) = split(self.region, kind, rule)
+                                              .ok_or(LayoutError::TooSmall(self.tag))?;
+        let grid = match self.panels.pop() {
+            Some(Panel::Grid(grid)) => grid,
+            Some(panel)             => {
+                self.panels.push(panel);
+                return Err(LayoutError::NotAGrid(self.tag))
+            }
+            None                    => return Err(LayoutError::NotAGrid(self.tag)),
+        };
+        let (mut left, mut right) = (Section::new(tags.0, left_region),
+                                     Section::new(tags.1, right_region));
+        let saved = match save {
+            SaveGrid::Left  => &mut left,
+            SaveGrid::Right => &mut right,
+        };
+        saved.panels = vec![Panel::Grid(grid)];
+        saved.resize(saved.region, rule);
+        self.panels.push(Panel::Split {
+            kind: kind,
+            rule: rule,
+            children: Box::new((left, right)),
+        });
+        Ok(())
+    }
+
+    /// Replace the split at the top of this section with the panels of one side of it.
+    pub fn unsplit(&mut self, save: SaveGrid) -> Result<(), LayoutError> {
+        let (rule, children) = match self.panels.pop() {
+            Some(Panel::Split { rule, children, .. })   => (rule, children),
+            Some(panel)                                 => {
+                self.panels.push(panel);
+                return Err(LayoutError::NotASplit(self.tag))
+            }
+            None                                        => {
+                return Err(LayoutError::NotASplit(self.tag))
+            }
+        };
+        let (left, right) = *children;
+        self.panels.extend(match save {
+            SaveGrid::Left  => left.panels,
+            SaveGrid::Right => right.panels,
+        });
+        let region = self.region;
+        self.resize(region, rule);
+        Ok(())
+    }
+
+    /// Move the split at the top of this section.
+    pub fn adjust(&mut self, new_kind: SplitKind, new_rule: ResizeRule)
+            -> Result<(), LayoutError> {
+        let region = self.region;
+        if split(region, new_kind, new_rule).is_none() {
+            return Err(LayoutError::TooSmall(self.tag))
+        }
+        match self.panels.last_mut() {
+            Some(Panel::Split { kind, rule, .. })   => {
+                *kind = new_kind;
+                *rule = new_rule;
+            }
+            _                                       => {
+                return Err(LayoutError::NotASplit(self.tag))
+            }
+        }
+        self.resize(region, new_rule);
+        Ok(())
+    }
+
+    /// Move this section into a new region, resizing every panel inside of it.
+    pub fn resize(&mut self, region: Region, rule: ResizeRule) {
+        self.region = region;
+        for panel in &mut self.panels {
+            match *panel {
+                Panel::Grid(ref mut grid)                                   => {
+                    grid.resize(region, rule)
+                }
+                Panel::Split { kind, rule: split_rule, ref mut children }   => {
+                    // Sections which are too small to split share the whole region.
+                    let (left, right) = split(region, kind, split_rule)
+                                            .unwrap_or((region, region));
+                    children.0.resize(left, rule);
+                    children.1.resize(right, rule);
+                }
+            }
+        }
+    }
+}
+
+// Split a region, returning None if either side of the split would be empty.
+fn split(region: Region, kind: SplitKind, rule: ResizeRule) -> Option<(Region, Region)> {
+    if region.is_empty() { return None }
+    let (_, left, right) = region.split(kind, rule);
+    if left.is_empty() || right.is_empty() { None } else { Some((left, right)) }
+}
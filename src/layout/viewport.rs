@@ -0,0 +1,180 @@
+use std::cmp;
+
+use args::{Coords, Direction, Region};
+use cmds::ScrollScreen;
+
+/// The part of some content which is on screen, following the cursor as it moves.
+///
+/// The viewport keeps the cursor at least a margin of cells away from each of its edges, unless
+/// the viewport is already at the edge of the content. It never shows anything beyond the
+/// content, except when the content is smaller than the viewport, in which case the viewport
+/// stays at the top left corner of the content.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Viewport {
+    region: Region,
+    width: u32,
+    height: u32,
+    margins: (u32, u32),
+}
+
+impl Viewport {
+    /// Create a viewport showing a region of content of the given size, with no margins. The
+    /// region must not be empty.
+    pub fn new(region: Region, width: u32, height: u32) -> Viewport {
+        let mut viewport = Viewport {
+            region: region,
+            width: width,
+            height: height,
+            margins: (0, 0),
+        };
+        viewport.region = viewport.clamp(region);
+        viewport
+    }
+
+    /// Set the horizontal and vertical margins kept between the cursor and the edges of the
+    /// viewport. Margins too large for the viewport are reduced, so that there is always a cell
+    /// the cursor can be in without moving the viewport.
+    pub fn with_margins(self, horizontal: u32, vertical: u32) -> Viewport {
+        let horizontal = cmp::min(horizontal, (self.region.width() - 1) / 2);
+        let vertical = cmp::min(vertical, (self.region.height() - 1) / 2);
+        Viewport { margins: (horizontal, vertical), ..self }
+    }
+
+    /// The region of the content which is on screen.
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    pub fn margins(&self) -> (u32, u32) {
+        self.margins
+    }
+
+    /// Move the viewport as little as possible to keep the cursor within its margins, returning
+    /// the commands which scroll the screen to match.
+    pub fn follow(&mut self, cursor: Coords) -> Vec<ScrollScreen> {
+        let (mx, my) = self.margins;
+        let inner = Region {
+            left: self.region.left + mx,
+            top: self.region.top + my,
+            right: self.region.right - mx,
+            bottom: self.region.bottom - my,
+        };
+        let inner = inner.move_to_contain(cursor);
+        let region = Region {
+            left: inner.left.saturating_sub(mx),
+            top: inner.top.saturating_sub(my),
+            ..self.region
+        };
+        self.scroll_to(region.resized(self.region.width(), self.region.height()))
+    }
+
+    /// Change the size of the content, moving the viewport back onto the content if it no
+    /// longer fits. Returns the commands which scroll the screen to match.
+    pub fn set_content_size(&mut self, width: u32, height: u32) -> Vec<ScrollScreen> {
+        self.width = width;
+        self.height = height;
+        let region = self.region;
+        self.scroll_to(region)
+    }
+
+    fn scroll_to(&mut self, region: Region) -> Vec<ScrollScreen> {
+        let region = self.clamp(region);
+        let old = self.region;
+        self.region = region;
+        let mut scrolls = vec![];
+        // The contents of the screen move the opposite way to the viewport.
+        if region.top != old.top {
+            scrolls.push(match region.top > old.top {
+                true    => ScrollScreen::new(Direction::Up, region.top - old.top),
+                false   => ScrollScreen::new(Direction::Down, old.top - region.top),
+            });
+        }
+        if region.left != old.left {
+            scrolls.push(match region.left > old.left {
+                true    => ScrollScreen::new(Direction::Left, region.left - old.left),
+                false   => ScrollScreen::new(Direction::Right, old.left - region.left),
+            });
+        }
+        scrolls
+    }
+
+    // Move a region of the viewport's size onto the content.
+    fn clamp(&self, region: Region) -> Region {
+        let left = cmp::min(region.left, self.width.saturating_sub(region.width()));
+        let top = cmp::min(region.top, self.height.saturating_sub(region.height()));
+        Region { left: left, top: top, ..region }.resized(region.width(), region.height())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use args::{Coords, Region};
+    use args::Direction::*;
+    use cmds::ScrollScreen;
+
+    use super::Viewport;
+
+    fn viewport() -> Viewport {
+        Viewport::new(Region::new(40, 40, 60, 50), 100, 100).with_margins(2, 1)
+    }
+
+    #[test]
+    fn following_the_cursor() {
+        let moves = [
+            // Left and above
+            ((20, 30), Region::new(18, 29, 38, 39), vec![(Down, 11), (Right, 22)]),
+            // Left and below
+            ((20, 70), Region::new(18, 62, 38, 72), vec![(Up, 22), (Right, 22)]),
+            // Right and above
+            ((80, 30), Region::new(63, 29, 83, 39), vec![(Down, 11), (Left, 23)]),
+            // Right and below
+            ((80, 70), Region::new(63, 62, 83, 72), vec![(Up, 22), (Left, 23)]),
+            // Left only
+            ((20, 45), Region::new(18, 40, 38, 50), vec![(Right, 22)]),
+            // Right only
+            ((80, 45), Region::new(63, 40, 83, 50), vec![(Left, 23)]),
+            // Above only
+            ((50, 30), Region::new(40, 29, 60, 39), vec![(Down, 11)]),
+            // Below only
+            ((50, 70), Region::new(40, 62, 60, 72), vec![(Up, 22)]),
+            // Inside
+            ((50, 45), Region::new(40, 40, 60, 50), vec![]),
+            // Inside, but within the margins
+            ((41, 49), Region::new(39, 41, 59, 51), vec![(Up, 1), (Right, 1)]),
+        ];
+        for &((x, y), region, ref scrolls) in &moves {
+            let mut viewport = viewport();
+            let expected: Vec<_> = scrolls.iter().map(|&(dir, n)| ScrollScreen::new(dir, n))
+                                                 .collect();
+            assert_eq!(viewport.follow(Coords { x: x, y: y }), expected, "{}, {}", x, y);
+            assert_eq!(viewport.region(), region);
+        }
+    }
+
+    #[test]
+    fn clamping_to_the_content() {
+        let mut viewport = viewport();
+        viewport.follow(Coords { x: 0, y: 0 });
+        assert_eq!(viewport.region(), Region::new(0, 0, 20, 10));
+        viewport.follow(Coords { x: 99, y: 99 });
+        assert_eq!(viewport.region(), Region::new(80, 90, 100, 100));
+        assert_eq!(viewport.set_content_size(90, 95), [ScrollScreen::new(Down, 5),
+                                                        ScrollScreen::new(Right, 10)]);
+        assert_eq!(viewport.region(), Region::new(70, 85, 90, 95));
+
+        // Content smaller than the viewport is shown from its top left corner.
+        let mut viewport = Viewport::new(Region::new(3, 3, 23, 13), 10, 5);
+        assert_eq!(viewport.region(), Region::new(0, 0, 20, 10));
+        assert_eq!(viewport.follow(Coords { x: 9, y: 4 }), []);
+    }
+
+    #[test]
+    fn large_margins() {
+        let mut viewport = Viewport::new(Region::new(0, 0, 5, 4), 10, 10).with_margins(9, 9);
+        assert_eq!(viewport.margins(), (2, 1));
+        assert_eq!(viewport.follow(Coords { x: 3, y: 3 }), [ScrollScreen::new(Up, 1),
+                                                            ScrollScreen::new(Left, 1)]);
+        assert_eq!(viewport.region(), Region::new(1, 1, 6, 5));
+    }
+}
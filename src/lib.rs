@@ -1,10 +1,28 @@
-#![feature(associated_consts)]
+#![allow(clippy::redundant_field_names, clippy::redundant_static_lifetimes)]
 extern crate base64;
+#[macro_use]
+extern crate notty_encoding_derive;
 
+// Allows the code generated by `#[derive(Argument)]` to name this crate from inside of it.
+extern crate self as notty_encoding;
+
+pub mod ansi;
 pub mod args;
 pub mod cmds;
 pub mod client;
+pub mod layout;
+pub mod parser;
+pub mod screen;
+
+mod encode;
+mod error;
+
+#[cfg(test)]
+mod fuzz;
+
+pub use error::{DecodeError, ErrorKind, LayoutError, ParseColorError};
 
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum MediaFormat {
     Png, Gif, Jpeg,
 }
@@ -17,4 +35,13 @@ impl MediaFormat {
             MediaFormat::Jpeg   => "image/jpeg",
         }
     }
+
+    fn from_mime(mime: &str) -> Option<MediaFormat> {
+        match mime {
+            "image/png"     => Some(MediaFormat::Png),
+            "image/gif"     => Some(MediaFormat::Gif),
+            "image/jpeg"    => Some(MediaFormat::Jpeg),
+            _               => None,
+        }
+    }
 }
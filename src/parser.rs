@@ -0,0 +1,396 @@
+use std::str;
+
+use cmds::{Command, Control, Decoder};
+
+use self::State::*;
+
+const ESC: u8 = 0x1b;
+
+/// An item recognized in the output stream by a `Parser`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Event<'a> {
+    /// Text which is not part of a notty escape sequence. This includes any escape sequences
+    /// belonging to other protocols. A run of text may be split across several events.
+    Text(&'a str),
+    /// A complete notty escape sequence, decoded into a command.
+    Command(Command),
+    /// Bytes which could not be interpreted: either the body of a notty escape sequence which
+    /// failed to decode, or a byte sequence in the text which is not valid UTF-8.
+    Malformed(&'a [u8]),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum State {
+    /// Processing text.
+    Ground,
+    /// Seen `0xc2` at the start of a character; this may be the first byte of the UTF-8 encoded
+    /// C1 introducer.
+    GroundC1,
+    /// Seen `ESC`.
+    Escape,
+    /// Seen an introducer, in the given form.
+    Apc(Control),
+    /// Inside the body of a notty sequence.
+    Body,
+    /// Seen `ESC` inside the body; this should be the first byte of the 7-bit terminator.
+    BodyEscape,
+    /// Seen `0xc2` inside the body; this should be the first byte of the UTF-8 encoded C1
+    /// terminator.
+    BodyC1,
+}
+
+/// A resumable parser for output which mixes UTF-8 text with notty escape sequences.
+///
+/// Input can be fed to the parser in chunks of any size; escape sequences and characters which
+/// are split between chunks are buffered until the rest of them arrives. Sequences begin with
+/// `APC [` and end with `ST`, where each of the `APC` and `ST` controls may be in its 7-bit form
+/// (`ESC _` and `ESC \`), its UTF-8 encoded form, or as a single 8-bit byte. Sequences wrapped
+/// for passthrough are expected to have been unwrapped by the multiplexer.
+///
+/// Sequences are decoded with a `Decoder`; the parser never buffers more of a sequence than the
+/// `max_len` of its decoder allows.
+#[derive(Clone, Debug)]
+pub struct Parser {
+    state: State,
+    body: Vec<u8>,
+    partial: Vec<u8>,
+    // The number of continuation bytes expected to follow in the current character of text.
+    continuation: usize,
+    decoder: Decoder,
+}
+
+impl Parser {
+    /// Create a parser which decodes sequences leniently, without limits.
+    pub fn new() -> Parser {
+        Parser::with_decoder(Decoder::lenient())
+    }
+
+    /// Create a parser which decodes sequences with the given decoder.
+    pub fn with_decoder(decoder: Decoder) -> Parser {
+        Parser {
+            state: Ground,
+            body: Vec::new(),
+            partial: Vec::new(),
+            continuation: 0,
+            decoder: decoder,
+        }
+    }
+
+    /// Returns true if the parser is holding onto an incomplete escape sequence or character.
+    pub fn is_pending(&self) -> bool {
+        self.state != Ground || !self.partial.is_empty()
+    }
+
+    /// Parse a chunk of input, passing each event recognized in it to the callback.
+    pub fn feed<F>(&mut self, input: &[u8], mut f: F) where F: FnMut(Event) {
+        let mut text_start = 0;
+        let mut i = 0;
+        while i < input.len() {
+            let byte = input[i];
+            match self.state {
+                Ground      => match byte {
+                    ESC                                 => {
+                        self.text(&input[text_start..i], false, &mut f);
+                        self.continuation = 0;
+                        self.state = Escape;
+                    }
+                    0xc2 if self.continuation == 0      => {
+                        self.text(&input[text_start..i], false, &mut f);
+                        self.state = GroundC1;
+                    }
+                    0x9f if self.continuation == 0      => {
+                        self.text(&input[text_start..i], false, &mut f);
+                        self.state = Apc(Control::EightBit);
+                    }
+                    _                                   => {
+                        self.continuation = match byte {
+                            0x80..=0xbf => self.continuation.saturating_sub(1),
+                            0xc0..=0xdf => 1,
+                            0xe0..=0xef => 2,
+                            0xf0..=0xf7 => 3,
+                            _           => 0,
+                        };
+                    }
+                },
+                GroundC1    => match byte {
+                    0x9f    => self.state = Apc(Control::Utf8),
+                    _       => {
+                        // An ordinary character; the text method will complete it.
+                        self.partial.push(0xc2);
+                        self.continuation = 1;
+                        self.state = Ground;
+                        text_start = i;
+                        continue
+                    }
+                },
+                Escape      => match byte {
+                    b'_'    => self.state = Apc(Control::SevenBit),
+                    _       => {
+                        f(Event::Text("\x1b"));
+                        self.state = Ground;
+                        text_start = i;
+                        continue
+                    }
+                },
+                Apc(form)   => match byte {
+                    b'['    => {
+                        self.body.clear();
+                        self.state = Body;
+                    }
+                    _       => {
+                        introducer(form, &mut f);
+                        self.state = Ground;
+                        text_start = i;
+                        continue
+                    }
+                },
+                Body        => match byte {
+                    ESC     => self.state = BodyEscape,
+                    0xc2    => self.state = BodyC1,
+                    0x9c    => {
+                        self.finish_sequence(&mut f);
+                        text_start = i + 1;
+                    }
+                    _       => self.push(byte),
+                },
+                BodyEscape  => match byte {
+                    b'\\'   => {
+                        self.finish_sequence(&mut f);
+                        text_start = i + 1;
+                    }
+                    _       => {
+                        // The sequence was interrupted by another escape sequence.
+                        f(Event::Malformed(&self.body));
+                        self.body.clear();
+                        self.state = Escape;
+                        continue
+                    }
+                },
+                BodyC1      => match byte {
+                    0x9c    => {
+                        self.finish_sequence(&mut f);
+                        text_start = i + 1;
+                    }
+                    _       => {
+                        self.push(0xc2);
+                        self.state = Body;
+                        continue
+                    }
+                },
+            }
+            i += 1;
+        }
+        if self.state == Ground {
+            self.text(&input[text_start..], true, &mut f);
+        }
+    }
+
+    /// Signal the end of the input, passing anything the parser was still holding onto to the
+    /// callback.
+    pub fn finish<F>(&mut self, mut f: F) where F: FnMut(Event) {
+        if !self.partial.is_empty() {
+            f(Event::Malformed(&self.partial));
+            self.partial.clear();
+        }
+        match self.state {
+            Ground                          => {}
+            GroundC1                        => f(Event::Malformed(&[0xc2])),
+            Escape                          => f(Event::Text("\x1b")),
+            Apc(form)                       => introducer(form, &mut f),
+            Body | BodyEscape | BodyC1      => f(Event::Malformed(&self.body)),
+        }
+        self.state = Ground;
+        self.body.clear();
+        self.continuation = 0;
+    }
+
+    // Buffer a byte of the body of a sequence. Once the body exceeds the maximum length allowed by
+    // the decoder, further bytes are discarded; the decoder will reject the truncated body.
+    fn push(&mut self, byte: u8) {
+        if self.body.len() <= self.decoder.max_len {
+            self.body.push(byte);
+        }
+    }
+
+    fn finish_sequence<F>(&mut self, f: &mut F) where F: FnMut(Event) {
+        let decoder = self.decoder;
+        match str::from_utf8(&self.body).ok().and_then(|body| decoder.decode_body(body).ok()) {
+            Some(cmd)   => f(Event::Command(cmd)),
+            None        => f(Event::Malformed(&self.body)),
+        }
+        self.body.clear();
+        self.state = Ground;
+    }
+
+    // Emit a run of text. If chunk_end is true, an incomplete character at the end of the run
+    // may be completed by the next chunk of input, and so is held onto.
+    fn text<F>(&mut self, mut bytes: &[u8], chunk_end: bool, f: &mut F) where F: FnMut(Event) {
+        if !self.partial.is_empty() {
+            let width = utf8_width(self.partial[0]);
+            while self.partial.len() < width && !bytes.is_empty() && bytes[0] & 0xc0 == 0x80 {
+                self.partial.push(bytes[0]);
+                bytes = &bytes[1..];
+            }
+            if self.partial.len() == width {
+                match str::from_utf8(&self.partial) {
+                    Ok(s)   => f(Event::Text(s)),
+                    Err(_)  => f(Event::Malformed(&self.partial)),
+                }
+            } else if bytes.is_empty() && chunk_end {
+                return
+            } else {
+                f(Event::Malformed(&self.partial));
+            }
+            self.partial.clear();
+        }
+
+        loop {
+            match str::from_utf8(bytes) {
+                Ok(s)   => {
+                    if !s.is_empty() { f(Event::Text(s)) }
+                    return
+                }
+                Err(err) => {
+                    let (valid, rest) = bytes.split_at(err.valid_up_to());
+                    if !valid.is_empty() {
+                        f(Event::Text(unsafe { str::from_utf8_unchecked(valid) }));
+                    }
+                    match err.error_len() {
+                        Some(n)                 => {
+                            f(Event::Malformed(&rest[..n]));
+                            bytes = &rest[n..];
+                        }
+                        None if chunk_end       => {
+                            self.partial.extend_from_slice(rest);
+                            return
+                        }
+                        None                    => {
+                            f(Event::Malformed(rest));
+                            return
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Parser {
+        Parser::new()
+    }
+}
+
+// Emit an introducer which turned out not to begin a notty sequence.
+fn introducer<F>(form: Control, f: &mut F) where F: FnMut(Event) {
+    match form {
+        Control::SevenBit   => f(Event::Text("\x1b_")),
+        Control::Utf8       => f(Event::Text("\u{9f}")),
+        Control::EightBit   => f(Event::Malformed(&[0x9f])),
+    }
+}
+
+fn utf8_width(byte: u8) -> usize {
+    match byte {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        _           => 4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use args::{Area, Direction, Movement};
+    use cmds::{EscCode, Encoder, Erase, Move};
+
+    #[derive(Debug, Eq, PartialEq)]
+    enum Owned {
+        Text(String),
+        Command(Command),
+        Malformed(Vec<u8>),
+    }
+
+    // Feed the input in chunks of the given size, joining adjacent runs of text.
+    fn parse(input: &[u8], chunk: usize) -> Vec<Owned> {
+        let mut events = Vec::new();
+        {
+            let mut push = |event: Event| match event {
+                Event::Text(s)      => match events.last_mut() {
+                    Some(&mut Owned::Text(ref mut text))    => text.push_str(s),
+                    _                                       => {
+                        events.push(Owned::Text(String::from(s)))
+                    }
+                },
+                Event::Command(cmd) => events.push(Owned::Command(cmd)),
+                Event::Malformed(b) => events.push(Owned::Malformed(b.to_vec())),
+            };
+            let mut parser = Parser::new();
+            for chunk in input.chunks(chunk) {
+                parser.feed(chunk, &mut push);
+            }
+            parser.finish(&mut push);
+        }
+        events
+    }
+
+    fn text(s: &str) -> Owned {
+        Owned::Text(String::from(s))
+    }
+
+    #[test]
+    fn parse_in_any_chunk_size() {
+        let erase = Erase::new(Area::CursorRow);
+        let mov = Move::new(Movement::To(Direction::Up, 2, false));
+        let input = format!("héllo{}wörld\x1b[1m{}{}", erase.encode(), mov.encode(),
+                            mov.encode().replace("\u{9c}", "\x1b\\"));
+        let expected = vec![
+            text("héllo"),
+            Owned::Command(erase.into()),
+            text("wörld\x1b[1m"),
+            Owned::Command(mov.into()),
+            Owned::Command(mov.into()),
+        ];
+        for chunk in 1..input.len() + 1 {
+            assert_eq!(parse(input.as_bytes(), chunk), expected, "chunk size {}", chunk);
+        }
+    }
+
+    #[test]
+    fn parse_any_framing() {
+        let mov = Move::new(Movement::To(Direction::Up, 2, false));
+        let forms = [Control::SevenBit, Control::EightBit, Control::Utf8];
+        for &introducer in &forms {
+            for &terminator in &forms {
+                let encoder = Encoder { introducer: introducer, terminator: terminator,
+                                        ..Encoder::new() };
+                let mut input = "ß\u{9f}".as_bytes().to_vec();
+                input.extend(encoder.encode(&mov));
+                input.extend("é".as_bytes());
+                let expected = vec![text("ß\u{9f}"), Owned::Command(mov.into()), text("é")];
+                for chunk in 1..input.len() + 1 {
+                    assert_eq!(parse(&input, chunk), expected, "{:?}, chunk size {}", encoder,
+                               chunk);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parse_malformed() {
+        assert_eq!(parse(b"a\x1b_[zz\x1b\\b", 2),
+                   vec![text("a"), Owned::Malformed(b"zz".to_vec()), text("b")]);
+        assert_eq!(parse(b"a\xffb\xe2\x82", 1),
+                   vec![text("a"), Owned::Malformed(vec![0xff]), text("b"),
+                        Owned::Malformed(vec![0xe2, 0x82])]);
+        assert_eq!(parse(b"\x1b_[20\x1b_x", 3),
+                   vec![Owned::Malformed(b"20".to_vec()), text("\x1b_x")]);
+        assert_eq!(parse(b"\x1b_[20", 4), vec![Owned::Malformed(b"20".to_vec())]);
+        assert_eq!(parse(b"a\x9fb\xc2", 1),
+                   vec![text("a"), Owned::Malformed(vec![0x9f]), text("b"),
+                        Owned::Malformed(vec![0xc2])]);
+    }
+
+}
@@ -0,0 +1,248 @@
+use std::cmp;
+use std::collections::BTreeSet;
+use std::rc::Rc;
+
+use args::{Area, Coords, Direction, Movement, Region, ResizeRule, TextStyle};
+use args::Direction::*;
+use args::Movement::*;
+use cmds::*;
+use layout::Pane;
+use MediaFormat;
+
+use super::{Cell, Content, Grid, Hyperlink, ToolTip};
+
+const TAB_WIDTH: u32 = 8;
+
+/// The contents of a grid panel: its cells, its cursor and the tooltips and hyperlinks attached
+/// to its cells.
+///
+/// Coordinates are relative to the top left corner of the panel. The model does not keep any
+/// content which is scrolled off of the grid.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Buffer {
+    cells: Grid<Cell>,
+    cursor: Coords,
+    // Set when a character is written in the last column. The cursor stays on that character
+    // until the next one is written, which goes at the start of the next row.
+    wrap_pending: bool,
+    tab_stops: BTreeSet<u32>,
+}
+
+impl Buffer {
+    /// Create an empty buffer. A buffer is always at least one cell wide and one cell high.
+    pub fn new(width: u32, height: u32) -> Buffer {
+        let (width, height) = (cmp::max(width, 1), cmp::max(height, 1));
+        Buffer {
+            cells: Grid::new(width, height),
+            cursor: Coords::default(),
+            wrap_pending: false,
+            tab_stops: tab_stops(width),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.cells.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.cells.height()
+    }
+
+    /// The region covered by this buffer, with its top left corner at the origin.
+    pub fn bounds(&self) -> Region {
+        self.cells.bounds()
+    }
+
+    /// The cells of this buffer.
+    pub fn cells(&self) -> &Grid<Cell> {
+        &self.cells
+    }
+
+    pub fn cursor(&self) -> Coords {
+        self.cursor
+    }
+
+    pub fn cell(&self, coords: Coords) -> Option<&Cell> {
+        self.cells.get(coords)
+    }
+
+    /// The characters in a row, with empty cells and media as spaces and trailing spaces removed.
+    pub fn text(&self, y: u32) -> String {
+        if y >= self.height() { return String::new() }
+        let row: String = self.cells.row(y).iter().map(|cell| {
+            match cell.content {
+                Content::Char(c)    => c,
+                _                   => ' ',
+            }
+        }).collect();
+        String::from(row.trim_end())
+    }
+
+    pub fn tooltip(&self, coords: Coords) -> Option<&ToolTip> {
+        self.cells.get(coords).and_then(|cell| cell.tooltip.as_deref())
+    }
+
+    pub fn hyperlink(&self, coords: Coords) -> Option<&Hyperlink> {
+        self.cells.get(coords).and_then(|cell| cell.link.as_deref())
+    }
+
+    /// Write text at the cursor in the given style, linking it to a hyperlink if there is one.
+    /// Newlines, carriage returns, tabs and backspaces move the cursor; other control characters
+    /// are ignored. Text which continues past the end of a row wraps onto the next row, scrolling
+    /// the grid at the bottom.
+    pub fn write(&mut self, text: &str, style: TextStyle, link: Option<&Rc<Hyperlink>>) {
+        for c in text.chars() {
+            match c {
+                '\n'                    => self.move_cursor(NextLine(1)),
+                '\r'                    => self.move_cursor(ToEdge(Left)),
+                '\t'                    => self.move_cursor(Tab(Right, 1, false)),
+                '\x08'                  => self.move_cursor(To(Left, 1, false)),
+                c if c.is_control()     => {}
+                c                       => {
+                    if self.wrap_pending {
+                        self.move_cursor(NextLine(1));
+                    }
+                    let cell = &mut self.cells[self.cursor];
+                    cell.content = Content::Char(c);
+                    cell.style = style;
+                    cell.link = link.cloned();
+                    if self.cursor.x + 1 < self.width() {
+                        self.cursor.x += 1;
+                    } else {
+                        self.wrap_pending = true;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Apply a command which acts on the contents of a grid. Other commands are ignored.
+    pub fn apply(&mut self, cmd: &Command) {
+        self.wrap_pending = false;
+        match *cmd {
+            Command::Move(Move { movement })                    => self.move_cursor(movement),
+            Command::ScrollScreen(ScrollScreen { dir, n })      => self.scroll(dir, n),
+            Command::Erase(Erase { area })                      => {
+                self.each_cell(area, |cell| *cell = Cell::default())
+            }
+            Command::RemoveChars(RemoveChars { count })         => {
+                self.cells.remove_cells(self.cursor, count)
+            }
+            Command::InsertBlank(InsertBlank { count })         => {
+                self.cells.insert_cells(self.cursor, count)
+            }
+            Command::RemoveRows(RemoveRows { count, include })  => {
+                let y = if include { self.cursor.y } else { self.cursor.y + 1 };
+                self.cells.remove_rows(y, count)
+            }
+            Command::InsertRows(InsertRows { count, include })  => {
+                let y = if include { self.cursor.y } else { self.cursor.y + 1 };
+                self.cells.insert_rows(y, count)
+            }
+            Command::SetStyleInArea(SetStyleInArea(area, ref styles))   => {
+                self.each_cell(area, |cell| {
+                    styles.iter().for_each(|&style| cell.style.apply(style))
+                })
+            }
+            Command::DefaultStyleInArea(DefaultStyleInArea(area))   => {
+                self.each_cell(area, |cell| cell.style = TextStyle::default())
+            }
+            Command::PutMedia(ref media)                        => {
+                let cursor = self.cursor;
+                self.put_media(cursor, media.width, media.height, media.fmt)
+            }
+            Command::PutMediaAt(ref media)                      => {
+                self.put_media(media.coords, media.width, media.height, media.fmt)
+            }
+            Command::AddToolTip(AddToolTip(coords, ref text))   => {
+                self.add_tooltip(coords, ToolTip::Text(text.clone()))
+            }
+            Command::AddDropDown(ref dropdown)                  => {
+                self.add_tooltip(dropdown.coords, ToolTip::DropDown(dropdown.options.clone()))
+            }
+            Command::RemoveToolTip(RemoveToolTip(coords))       => {
+                if let Some(cell) = self.cells.get_mut(coords) {
+                    cell.tooltip = None;
+                }
+            }
+            Command::AddHyperlink(AddHyperlink(area, ref url))  => {
+                let link = Rc::new(Hyperlink { id: None, url: url.clone() });
+                self.each_cell(area, |cell| cell.link = Some(link.clone()))
+            }
+            _                                                   => {}
+        }
+    }
+
+    /// Resize the buffer. When it shrinks, `MaxRightBottom` keeps the cells at the right and
+    /// bottom of the buffer; the other rules keep the cells at the left and top.
+    pub fn resize(&mut self, width: u32, height: u32, rule: ResizeRule) {
+        let (width, height) = (cmp::max(width, 1), cmp::max(height, 1));
+        let (dx, dy) = match rule {
+            ResizeRule::MaxRightBottom  => {
+                (self.width().saturating_sub(width), self.height().saturating_sub(height))
+            }
+            _                           => (0, 0),
+        };
+        self.cells.resize(width, height, rule);
+        let bounds = self.bounds();
+        let cursor = Coords {
+            x: self.cursor.x.saturating_sub(dx),
+            y: self.cursor.y.saturating_sub(dy),
+        };
+        self.cursor = bounds.xy_within(cursor);
+        self.wrap_pending = false;
+        self.tab_stops = tab_stops(width);
+    }
+
+    fn move_cursor(&mut self, movement: Movement) {
+        self.wrap_pending = false;
+        let (cursor, scrolled) = movement.apply(self.cursor, self.bounds(), &self.tab_stops);
+        if scrolled > 0 {
+            // Moving past the bottom of the grid scrolls its contents up, and vice versa.
+            self.scroll(movement.direction(self.cursor).rev(), scrolled);
+        }
+        self.cursor = cursor;
+    }
+
+    // Scroll the contents of the grid in a direction, filling the space left with empty cells.
+    fn scroll(&mut self, dir: Direction, n: u32) {
+        let bounds = self.bounds();
+        self.cells.scroll(bounds, dir, n);
+    }
+
+    fn each_cell<F>(&mut self, area: Area, mut f: F) where F: FnMut(&mut Cell) {
+        for (_, cell) in self.cells.area_mut(area, self.cursor, &self.tab_stops) {
+            f(cell);
+        }
+    }
+
+    fn put_media(&mut self, coords: Coords, width: u32, height: u32, fmt: MediaFormat) {
+        let right = cmp::min(coords.x as u64 + width as u64, self.width() as u64) as u32;
+        let bottom = cmp::min(coords.y as u64 + height as u64, self.height() as u64) as u32;
+        for y in coords.y..bottom {
+            for x in coords.x..right {
+                self.cells[Coords { x: x, y: y }].content = Content::Media(fmt);
+            }
+        }
+    }
+
+    fn add_tooltip(&mut self, coords: Coords, tooltip: ToolTip) {
+        if let Some(cell) = self.cells.get_mut(coords) {
+            cell.tooltip = Some(Rc::new(tooltip));
+        }
+    }
+}
+
+impl Pane for Buffer {
+    fn empty(region: Region) -> Buffer {
+        Buffer::new(region.width(), region.height())
+    }
+
+    fn resize(&mut self, region: Region, rule: ResizeRule) {
+        Buffer::resize(self, region.width(), region.height(), rule)
+    }
+}
+
+fn tab_stops(width: u32) -> BTreeSet<u32> {
+    (1..width.div_ceil(TAB_WIDTH)).map(|n| n * TAB_WIDTH).collect()
+}
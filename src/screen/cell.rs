@@ -0,0 +1,41 @@
+use std::rc::Rc;
+
+use args::TextStyle;
+use MediaFormat;
+
+/// A single cell of a grid.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Cell {
+    pub content: Content,
+    pub style: TextStyle,
+    /// The tooltip attached to this cell, which moves and is erased along with it.
+    pub tooltip: Option<Rc<ToolTip>>,
+    /// The hyperlink the content of this cell is linked to, which is shared by every cell linked
+    /// at the same time.
+    pub link: Option<Rc<Hyperlink>>,
+}
+
+/// What a cell contains.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Content {
+    #[default]
+    Empty,
+    Char(char),
+    /// Part of a media object.
+    Media(MediaFormat),
+}
+
+/// Text attached to a cell of a grid.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ToolTip {
+    Text(String),
+    DropDown(Vec<String>),
+}
+
+/// A link from a cell of a grid to a URL.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Hyperlink {
+    /// Cells linked with the same id are part of the same link.
+    pub id: Option<u64>,
+    pub url: String,
+}
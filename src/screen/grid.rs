@@ -0,0 +1,354 @@
+use std::cmp;
+use std::collections::BTreeSet;
+use std::mem;
+use std::ops::{Index, IndexMut};
+
+use args::{Area, Coords, Direction, Region, ResizeRule};
+use args::Direction::*;
+
+/// A two dimensional array of values, indexed by their coordinates.
+///
+/// Values are stored row by row, with the top left value at the origin. Indexing outside of the
+/// grid panics; `get` and `get_mut` return `None` instead. Space left by scrolling, inserting,
+/// removing or growing is filled with default values.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Grid<T> {
+    width: u32,
+    height: u32,
+    cells: Vec<T>,
+}
+
+impl<T> Grid<T> {
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The region covered by this grid, with its top left corner at the origin.
+    pub fn bounds(&self) -> Region {
+        Region { left: 0, top: 0, right: self.width, bottom: self.height }
+    }
+
+    pub fn get(&self, coords: Coords) -> Option<&T> {
+        if self.bounds().contains(coords) { Some(&self.cells[self.index(coords)]) } else { None }
+    }
+
+    pub fn get_mut(&mut self, coords: Coords) -> Option<&mut T> {
+        if self.bounds().contains(coords) {
+            let idx = self.index(coords);
+            Some(&mut self.cells[idx])
+        } else { None }
+    }
+
+    /// The values in a row, from left to right. Panics if the row is not in the grid.
+    pub fn row(&self, y: u32) -> &[T] {
+        assert!(y < self.height, "row {} is outside of the grid", y);
+        let width = self.width as usize;
+        &self.cells[y as usize * width..][..width]
+    }
+
+    pub fn row_mut(&mut self, y: u32) -> &mut [T] {
+        assert!(y < self.height, "row {} is outside of the grid", y);
+        let width = self.width as usize;
+        &mut self.cells[y as usize * width..][..width]
+    }
+
+    /// The values in a column, from top to bottom. Panics if the column is not in the grid.
+    pub fn column(&self, x: u32) -> impl Iterator<Item=&T> {
+        assert!(x < self.width, "column {} is outside of the grid", x);
+        self.cells.iter().skip(x as usize).step_by(self.width as usize)
+    }
+
+    pub fn column_mut(&mut self, x: u32) -> impl Iterator<Item=&mut T> {
+        assert!(x < self.width, "column {} is outside of the grid", x);
+        let width = self.width as usize;
+        self.cells.iter_mut().skip(x as usize).step_by(width)
+    }
+
+    /// Every value in the grid with its coordinates, row by row.
+    pub fn iter(&self) -> impl Iterator<Item=(Coords, &T)> {
+        let bounds = self.bounds();
+        bounds.cells().zip(&self.cells)
+    }
+
+    /// The values in the cells of an area, with their coordinates, in reading order. The area is
+    /// resolved as it would be for a cursor at the given coordinates.
+    pub fn area<'a>(&'a self, area: Area, cursor: Coords, tab_stops: &BTreeSet<u32>)
+            -> impl Iterator<Item=(Coords, &'a T)> + 'a {
+        let cells = area.resolve(cursor, self.bounds(), tab_stops);
+        self.iter().filter(move |&(coords, _)| cells.contains(coords))
+    }
+
+    pub fn area_mut<'a>(&'a mut self, area: Area, cursor: Coords, tab_stops: &BTreeSet<u32>)
+            -> impl Iterator<Item=(Coords, &'a mut T)> + 'a {
+        let bounds = self.bounds();
+        let cells = area.resolve(cursor, bounds, tab_stops);
+        bounds.cells().zip(&mut self.cells).filter(move |&(coords, _)| cells.contains(coords))
+    }
+
+    fn index(&self, coords: Coords) -> usize {
+        coords.y as usize * self.width as usize + coords.x as usize
+    }
+}
+
+impl<T: Default> Grid<T> {
+    /// Create a grid of default values.
+    pub fn new(width: u32, height: u32) -> Grid<T> {
+        Grid {
+            width: width,
+            height: height,
+            cells: (0..width as usize * height as usize).map(|_| T::default()).collect(),
+        }
+    }
+
+    /// Resize the grid. When it shrinks, `MaxRightBottom` keeps the values at the right and
+    /// bottom of the grid; the other rules keep the values at the left and top.
+    pub fn resize(&mut self, width: u32, height: u32, rule: ResizeRule) {
+        let (dx, dy) = match rule {
+            ResizeRule::MaxRightBottom  => {
+                (self.width.saturating_sub(width), self.height.saturating_sub(height))
+            }
+            _                           => (0, 0),
+        };
+        let mut grid = Grid::new(width, height);
+        for y in 0..cmp::min(height, self.height - dy) {
+            for x in 0..cmp::min(width, self.width - dx) {
+                let idx = self.index(Coords { x: x + dx, y: y + dy });
+                grid[Coords { x: x, y: y }] = mem::take(&mut self.cells[idx]);
+            }
+        }
+        *self = grid;
+    }
+
+    /// Scroll the values within a region of the grid by `n` cells in a direction. Values moved
+    /// out of the region are dropped and the space left is filled with default values; the rest
+    /// of the grid is unchanged.
+    pub fn scroll(&mut self, region: Region, dir: Direction, n: u32) {
+        let region = match region.intersection(self.bounds()) {
+            Some(region)    => region,
+            None            => return,
+        };
+        let n = cmp::min(n, match dir {
+            Up | Down       => region.height(),
+            Left | Right    => region.width(),
+        });
+        // Each value is moved from the cell `n` away against the direction of the scroll, so the
+        // cells are visited starting from the edge the values move towards.
+        let mut coords: Vec<Coords> = region.cells().collect();
+        if let Down | Right = dir {
+            coords.reverse();
+        }
+        for to in coords {
+            let from = match dir {
+                Up      => Coords { x: to.x, y: to.y + n },
+                Down    => Coords { x: to.x, y: to.y.wrapping_sub(n) },
+                Left    => Coords { x: to.x + n, y: to.y },
+                Right   => Coords { x: to.x.wrapping_sub(n), y: to.y },
+            };
+            self[to] = match region.contains(from) {
+                true    => mem::take(&mut self[from]),
+                false   => T::default(),
+            };
+        }
+    }
+
+    /// Insert rows of default values at row `y`, moving the rows below them down and off of the
+    /// bottom of the grid, like `InsertRows`.
+    pub fn insert_rows(&mut self, y: u32, n: u32) {
+        if let Some(region) = self.rows_from(y) {
+            self.scroll(region, Down, n);
+        }
+    }
+
+    /// Remove rows starting at row `y`, moving the rows below them up and filling the bottom of
+    /// the grid with default values, like `RemoveRows`.
+    pub fn remove_rows(&mut self, y: u32, n: u32) {
+        if let Some(region) = self.rows_from(y) {
+            self.scroll(region, Up, n);
+        }
+    }
+
+    /// Insert default values at the given coordinates, moving the rest of the row right and off
+    /// of the end of the row, like `InsertBlank`.
+    pub fn insert_cells(&mut self, coords: Coords, n: u32) {
+        if let Some(region) = self.row_from(coords) {
+            self.scroll(region, Right, n);
+        }
+    }
+
+    /// Remove values starting at the given coordinates, moving the rest of the row left and
+    /// filling the end of the row with default values, like `RemoveChars`.
+    pub fn remove_cells(&mut self, coords: Coords, n: u32) {
+        if let Some(region) = self.row_from(coords) {
+            self.scroll(region, Left, n);
+        }
+    }
+
+    // The rows from row y to the bottom of the grid.
+    fn rows_from(&self, y: u32) -> Option<Region> {
+        if y >= self.height { return None }
+        Region::checked_new(0, y, self.width, self.height)
+    }
+
+    // The cells from the given coordinates to the end of their row.
+    fn row_from(&self, coords: Coords) -> Option<Region> {
+        if !self.bounds().contains(coords) { return None }
+        Region::checked_new(coords.x, coords.y, self.width, coords.y + 1)
+    }
+}
+
+impl<T> Index<Coords> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, coords: Coords) -> &T {
+        assert!(self.bounds().contains(coords), "{:?} is outside of the grid", coords);
+        &self.cells[Grid::index(self, coords)]
+    }
+}
+
+impl<T> IndexMut<Coords> for Grid<T> {
+    fn index_mut(&mut self, coords: Coords) -> &mut T {
+        assert!(self.bounds().contains(coords), "{:?} is outside of the grid", coords);
+        let idx = Grid::index(self, coords);
+        &mut self.cells[idx]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::BTreeSet;
+
+    use args::{Area, Coords, Region, ResizeRule};
+    use args::Direction::*;
+    use args::Movement::*;
+
+    use super::Grid;
+
+    // A 4x3 grid with the value 10y + x + 1 in each cell.
+    fn grid() -> Grid<u32> {
+        let mut grid = Grid::new(4, 3);
+        for y in 0..3 {
+            for x in 0..4 {
+                grid[Coords { x: x, y: y }] = y * 10 + x + 1;
+            }
+        }
+        grid
+    }
+
+    fn rows(grid: &Grid<u32>) -> Vec<Vec<u32>> {
+        (0..grid.height()).map(|y| grid.row(y).to_vec()).collect()
+    }
+
+    #[test]
+    fn indexing() {
+        let mut grid = grid();
+        assert_eq!(grid[Coords { x: 2, y: 1 }], 13);
+        assert_eq!(grid.get(Coords { x: 3, y: 2 }), Some(&24));
+        assert_eq!(grid.get(Coords { x: 4, y: 0 }), None);
+        assert_eq!(grid.get(Coords { x: 0, y: 3 }), None);
+        assert_eq!(grid.row(1), [11, 12, 13, 14]);
+        assert_eq!(grid.column(2).cloned().collect::<Vec<_>>(), [3, 13, 23]);
+        grid.row_mut(0)[0] = 0;
+        for value in grid.column_mut(3) {
+            *value = 0;
+        }
+        *grid.get_mut(Coords { x: 1, y: 2 }).unwrap() = 0;
+        assert_eq!(rows(&grid), [[0, 2, 3, 0], [11, 12, 13, 0], [21, 0, 23, 0]]);
+        assert_eq!(grid.iter().nth(5), Some((Coords { x: 1, y: 1 }, &12)));
+        assert_eq!(grid.bounds(), Region::new(0, 0, 4, 3));
+    }
+
+    #[test]
+    #[should_panic]
+    fn indexing_outside_of_the_grid() {
+        let _ = grid()[Coords { x: 4, y: 0 }];
+    }
+
+    #[test]
+    fn resizing() {
+        let mut grid = grid();
+        grid.resize(3, 2, ResizeRule::MaxLeftTop);
+        assert_eq!(rows(&grid), [[1, 2, 3], [11, 12, 13]]);
+        grid.resize(4, 3, ResizeRule::Percentage);
+        assert_eq!(rows(&grid), [[1, 2, 3, 0], [11, 12, 13, 0], [0, 0, 0, 0]]);
+
+        let mut grid = self::grid();
+        grid.resize(2, 2, ResizeRule::MaxRightBottom);
+        assert_eq!(rows(&grid), [[13, 14], [23, 24]]);
+        grid.resize(3, 1, ResizeRule::MaxRightBottom);
+        assert_eq!(rows(&grid), [[23, 24, 0]]);
+        grid.resize(0, 0, ResizeRule::MaxRightBottom);
+        assert_eq!(grid.iter().count(), 0);
+    }
+
+    #[test]
+    fn scrolling_within_a_region() {
+        let region = Region::new(1, 0, 3, 3);
+        let tests = [
+            (Up, 1, [[1, 12, 13, 4], [11, 22, 23, 14], [21, 0, 0, 24]]),
+            (Down, 2, [[1, 0, 0, 4], [11, 0, 0, 14], [21, 2, 3, 24]]),
+            (Left, 1, [[1, 3, 0, 4], [11, 13, 0, 14], [21, 23, 0, 24]]),
+            (Right, 5, [[1, 0, 0, 4], [11, 0, 0, 14], [21, 0, 0, 24]]),
+        ];
+        for &(dir, n, expected) in &tests {
+            let mut grid = grid();
+            grid.scroll(region, dir, n);
+            assert_eq!(rows(&grid), expected, "{:?} {}", dir, n);
+        }
+
+        // Only the part of the region inside of the grid is scrolled.
+        let mut grid = grid();
+        grid.scroll(Region::new(2, 1, 10, 10), Left, 1);
+        assert_eq!(rows(&grid), [[1, 2, 3, 4], [11, 12, 14, 0], [21, 22, 24, 0]]);
+        grid.scroll(Region::new(5, 5, 10, 10), Up, 1);
+        assert_eq!(rows(&grid), [[1, 2, 3, 4], [11, 12, 14, 0], [21, 22, 24, 0]]);
+    }
+
+    #[test]
+    fn inserting_and_removing() {
+        let mut grid = grid();
+        grid.insert_rows(1, 1);
+        assert_eq!(rows(&grid), [[1, 2, 3, 4], [0, 0, 0, 0], [11, 12, 13, 14]]);
+        grid.remove_rows(0, 1);
+        assert_eq!(rows(&grid), [[0, 0, 0, 0], [11, 12, 13, 14], [0, 0, 0, 0]]);
+        grid.insert_cells(Coords { x: 1, y: 1 }, 2);
+        assert_eq!(grid.row(1), [11, 0, 0, 12]);
+        grid.remove_cells(Coords { x: 0, y: 1 }, 3);
+        assert_eq!(grid.row(1), [12, 0, 0, 0]);
+
+        // Coordinates outside of the grid change nothing.
+        let before = grid.clone();
+        grid.insert_rows(3, 1);
+        grid.remove_rows(10, 1);
+        grid.insert_cells(Coords { x: 4, y: 0 }, 1);
+        grid.remove_cells(Coords { x: 0, y: 3 }, 1);
+        assert_eq!(grid, before);
+    }
+
+    #[test]
+    fn iterating_over_areas() {
+        let tab_stops = BTreeSet::new();
+        let cursor = Coords { x: 2, y: 1 };
+        let values = |grid: &Grid<u32>, area| {
+            grid.area(area, cursor, &tab_stops).map(|(_, &n)| n).collect::<Vec<_>>()
+        };
+        let mut grid = grid();
+        assert_eq!(values(&grid, Area::CursorCell), [13]);
+        assert_eq!(values(&grid, Area::CursorColumn), [3, 13, 23]);
+        assert_eq!(values(&grid, Area::CursorTo(To(Right, 3, true))), [13, 14, 21, 22]);
+        assert_eq!(values(&grid, Area::Bound(Region::new(1, 1, 3, 5))), [12, 13, 22, 23]);
+        for (coords, value) in grid.area_mut(Area::CursorRow, cursor, &tab_stops) {
+            *value = coords.x;
+        }
+        assert_eq!(grid.row(1), [0, 1, 2, 3]);
+
+        let mut empty = Grid::<u32>::new(0, 0);
+        assert_eq!(empty.area(Area::WholeScreen, cursor, &tab_stops).count(), 0);
+        assert_eq!(empty.area_mut(Area::CursorTo(To(Right, 3, true)), cursor, &tab_stops).count(),
+                   0);
+    }
+}
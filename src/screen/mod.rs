@@ -0,0 +1,424 @@
+//! A headless model of a notty terminal's screen.
+//!
+//! The `Screen` applies commands to an in-memory grid of cells, so that the output of a program
+//! can be checked without a real terminal. It is a reference model rather than an emulator: it
+//! keeps no scrollback, draws nothing and ignores the attachments of media objects.
+
+use std::cmp;
+use std::mem;
+use std::rc::Rc;
+
+use args::{InputSettings, Region, ResizeRule, TextStyle};
+use cmds::*;
+use layout::Layout;
+use parser::{Event, Parser};
+
+mod buffer;
+mod cell;
+mod grid;
+
+pub use self::buffer::Buffer;
+pub use self::cell::{Cell, Content, Hyperlink, ToolTip};
+pub use self::grid::Grid;
+
+/// A screen, divided into tagged sections which each hold a stack of panels.
+///
+/// The screen starts as a single section tagged 0 which covers all of it. Commands which draw
+/// act on the grid of the active section; panel commands are applied to the screen's `Layout`.
+/// Panel commands which cannot be applied, such as splitting a section into tags which are
+/// already in use, are ignored.
+#[derive(Clone, Debug)]
+pub struct Screen {
+    layout: Layout<Buffer>,
+    text_style: TextStyle,
+    cursor_style: TextStyle,
+    link: Option<Rc<Hyperlink>>,
+    title: String,
+    input_mode: InputSettings,
+    parser: Parser,
+}
+
+impl Screen {
+    /// Create a blank screen. A screen is always at least one cell wide and one cell high.
+    pub fn new(width: u32, height: u32) -> Screen {
+        let region = Region::new(0, 0, cmp::max(width, 1), cmp::max(height, 1));
+        Screen {
+            layout: Layout::new(region),
+            text_style: TextStyle::default(),
+            cursor_style: TextStyle::default(),
+            link: None,
+            title: String::new(),
+            input_mode: InputSettings::Ansi(false),
+            parser: Parser::new(),
+        }
+    }
+
+    /// Apply a command to the screen.
+    pub fn apply(&mut self, cmd: &Command) {
+        match *cmd {
+            Command::SetTextStyle(SetTextStyle(ref styles)) => {
+                styles.iter().for_each(|&style| self.text_style.apply(style))
+            }
+            Command::DefaultTextStyle(_)                    => {
+                self.text_style = TextStyle::default()
+            }
+            Command::SetCursorStyle(SetCursorStyle(ref styles)) => {
+                styles.iter().for_each(|&style| self.cursor_style.apply(style))
+            }
+            Command::DefaultCursorStyle(_)                  => {
+                self.cursor_style = TextStyle::default()
+            }
+            Command::BeginHyperlink(ref link)               => {
+                self.link = Some(Rc::new(Hyperlink { id: link.id, url: link.url.clone() }))
+            }
+            Command::EndHyperlink(_)                        => self.link = None,
+            Command::SetTitle(SetTitle(ref title))          => self.title = title.clone(),
+            Command::SetInputMode(SetInputMode(mode))       => self.input_mode = mode,
+            Command::HoldForInput(_)                        => {}
+            Command::PushPanel(_) | Command::PopPanel(_) | Command::SplitPanel(_)
+                | Command::UnsplitPanel(_) | Command::AdjustPanelSplit(_)
+                | Command::RotateSectionDown(_) | Command::RotateSectionUp(_)
+                | Command::SwitchActiveSection(_)          => {
+                let _ = self.layout.apply(cmd);
+            }
+            _                                               => self.grid_mut().apply(cmd),
+        }
+    }
+
+    /// Resize the screen, rescaling the sections it is split into and resizing each grid
+    /// following the rule. A screen is always at least one cell wide and one cell high.
+    pub fn resize(&mut self, width: u32, height: u32, rule: ResizeRule) {
+        let region = Region::new(0, 0, cmp::max(width, 1), cmp::max(height, 1));
+        self.layout.resize(region, rule);
+    }
+
+    /// Write text at the cursor of the active grid, in the current text style.
+    pub fn write(&mut self, text: &str) {
+        let style = self.text_style;
+        self.layout.active_grid_mut().write(text, style, self.link.as_ref());
+    }
+
+    /// Feed output from a program to the screen, writing its text and applying its commands.
+    /// Malformed sequences are ignored.
+    pub fn feed(&mut self, input: &[u8]) {
+        let mut parser = mem::take(&mut self.parser);
+        parser.feed(input, |event| match event {
+            Event::Text(text)       => self.write(text),
+            Event::Command(cmd)     => self.apply(&cmd),
+            Event::Malformed(_)     => {}
+        });
+        self.parser = parser;
+    }
+
+    /// The grid of the active section.
+    pub fn grid(&self) -> &Buffer {
+        self.layout.active_grid()
+    }
+
+    /// The grid at the top of the section with this tag, if there is one.
+    pub fn section_grid(&self, tag: u64) -> Option<&Buffer> {
+        self.layout.grid(tag)
+    }
+
+    /// The sections and panels of the screen.
+    pub fn layout(&self) -> &Layout<Buffer> {
+        &self.layout
+    }
+
+    pub fn text_style(&self) -> TextStyle {
+        self.text_style
+    }
+
+    pub fn cursor_style(&self) -> TextStyle {
+        self.cursor_style
+    }
+
+    /// The hyperlink which text is linked to as it is written, if any.
+    pub fn hyperlink(&self) -> Option<&Hyperlink> {
+        self.link.as_deref()
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn input_mode(&self) -> InputSettings {
+        self.input_mode
+    }
+
+    fn grid_mut(&mut self) -> &mut Buffer {
+        self.layout.active_grid_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use args::*;
+    use MediaFormat;
+
+    use super::*;
+
+    fn at(x: u32, y: u32) -> Coords {
+        Coords { x: x, y: y }
+    }
+
+    fn screen(width: u32, height: u32, rows: &[&str]) -> Screen {
+        let mut screen = Screen::new(width, height);
+        for (y, row) in rows.iter().enumerate() {
+            screen.apply(&Move::new(Movement::Position(at(0, y as u32))).into());
+            screen.write(row);
+        }
+        screen
+    }
+
+    fn rows(screen: &Screen) -> Vec<String> {
+        let grid = screen.grid();
+        (0..grid.height()).map(|y| grid.text(y)).collect()
+    }
+
+    #[test]
+    fn writing_and_moving() {
+        let mut screen = screen(6, 3, &["abcdefgh"]);
+        assert_eq!(rows(&screen), ["abcdef", "gh", ""]);
+        assert_eq!(screen.grid().cursor(), at(2, 1));
+        screen.apply(&Move::new(Movement::Position(at(1, 2))).into());
+        screen.write("x\t");
+        assert_eq!(rows(&screen), ["abcdef", "gh", " x"]);
+        assert_eq!(screen.grid().cursor(), at(5, 2));
+        screen.apply(&Move::new(Movement::ToBeginning).into());
+        screen.apply(&Move::new(Movement::To(Direction::Right, 8, true)).into());
+        assert_eq!(screen.grid().cursor(), at(2, 1));
+        screen.apply(&Move::new(Movement::IndexTo(Direction::Down, 3)).into());
+        assert_eq!(rows(&screen), [" x", "", ""]);
+        assert_eq!(screen.grid().cursor(), at(2, 2));
+    }
+
+    #[test]
+    fn erasing_and_styling_areas() {
+        let mut screen = screen(4, 3, &["abcd", "efgh", "ijkl"]);
+        screen.apply(&Move::new(Movement::Position(at(2, 0))).into());
+        screen.apply(&Erase::new(Area::CursorTo(Movement::To(Direction::Right, 3, true))).into());
+        assert_eq!(rows(&screen), ["ab", "  gh", "ijkl"]);
+        screen.apply(&SetStyleInArea(Area::CursorColumn, vec![Style::Bold(true)]).into());
+        let grid = screen.grid();
+        assert!((0..3).all(|y| grid.cell(at(2, y)).unwrap().style.bold));
+        assert!(!grid.cell(at(1, 1)).unwrap().style.bold);
+        screen.apply(&DefaultStyleInArea(Area::Rows(1, 2)).into());
+        assert!(!screen.grid().cell(at(2, 1)).unwrap().style.bold);
+        assert!(screen.grid().cell(at(2, 2)).unwrap().style.bold);
+        screen.apply(&Erase::new(Area::WholeScreen).into());
+        assert_eq!(rows(&screen), ["", "", ""]);
+    }
+
+    #[test]
+    fn feeding_output() {
+        let mut screen = Screen::new(8, 2);
+        let mut output = b"ab".to_vec();
+        output.extend(SetTitle(String::from("title")).encode().bytes());
+        output.extend(Move::new(Movement::NextLine(1)).encode().bytes());
+        output.extend(b"c\x1b_[ff#\x1b\\d");
+        let (first, second) = output.split_at(10);
+        screen.feed(first);
+        screen.feed(second);
+        assert_eq!(rows(&screen), ["ab", "cd"]);
+        assert_eq!(screen.title(), "title");
+    }
+
+    #[test]
+    fn text_style() {
+        let mut screen = Screen::new(4, 1);
+        screen.apply(&SetTextStyle(vec![Style::Italic(true)]).into());
+        screen.apply(&SetTextStyle(vec![Style::FgColor(Color::Palette(1))]).into());
+        screen.write("a");
+        screen.apply(&DefaultTextStyle.into());
+        screen.write("b");
+        let style = screen.grid().cell(at(0, 0)).unwrap().style;
+        assert!(style.italic);
+        assert_eq!(style.fg, Color::Palette(1));
+        assert_eq!(screen.grid().cell(at(1, 0)).unwrap().style, TextStyle::default());
+    }
+
+    #[test]
+    fn editing_rows_and_cells() {
+        let mut screen = screen(4, 4, &["abcd", "efgh", "ijkl", "mnop"]);
+        screen.apply(&Move::new(Movement::Position(at(1, 1))).into());
+        screen.apply(&RemoveRows::new(1, true).into());
+        assert_eq!(rows(&screen), ["abcd", "ijkl", "mnop", ""]);
+        screen.apply(&InsertRows::new(2, false).into());
+        assert_eq!(rows(&screen), ["abcd", "ijkl", "", ""]);
+        screen.apply(&RemoveChars::new(2).into());
+        assert_eq!(rows(&screen)[1], "il");
+        screen.apply(&InsertBlank::new(1).into());
+        assert_eq!(rows(&screen)[1], "i l");
+    }
+
+    #[test]
+    fn scrolling() {
+        let mut screen = screen(3, 3, &["abc", "def", "ghi"]);
+        screen.apply(&ScrollScreen::new(Direction::Up, 1).into());
+        assert_eq!(rows(&screen), ["def", "ghi", ""]);
+        screen.apply(&ScrollScreen::new(Direction::Down, 2).into());
+        assert_eq!(rows(&screen), ["", "", "def"]);
+        screen.apply(&ScrollScreen::new(Direction::Right, 1).into());
+        assert_eq!(rows(&screen), ["", "", " de"]);
+    }
+
+    #[test]
+    fn media_and_tooltips() {
+        let mut screen = Screen::new(4, 4);
+        let media = PutMediaAt::new(at(1, 1), 2, 5, MediaPosition::Fill, MediaFormat::Png, vec![]);
+        screen.apply(&media.into());
+        assert_eq!(screen.grid().cell(at(2, 3)).unwrap().content, Content::Media(MediaFormat::Png));
+        assert_eq!(screen.grid().cell(at(3, 3)).unwrap().content, Content::Empty);
+        screen.apply(&AddToolTip(at(1, 1), String::from("tip")).into());
+        screen.apply(&AddToolTip(at(9, 9), String::from("offscreen")).into());
+        assert_eq!(screen.grid().tooltip(at(1, 1)), Some(&ToolTip::Text(String::from("tip"))));
+        assert_eq!(screen.grid().tooltip(at(9, 9)), None);
+        screen.apply(&RemoveToolTip(at(1, 1)).into());
+        assert_eq!(screen.grid().tooltip(at(1, 1)), None);
+    }
+
+    #[test]
+    fn tooltips_move_with_their_cells() {
+        let mut screen = Screen::new(4, 3);
+        let tip = ToolTip::Text(String::from("tip"));
+        screen.apply(&AddToolTip(at(1, 1), String::from("tip")).into());
+        screen.write("a\nb");
+        assert_eq!(screen.grid().tooltip(at(1, 1)), Some(&tip));
+        screen.apply(&ScrollScreen::new(Direction::Up, 1).into());
+        assert_eq!(screen.grid().tooltip(at(1, 1)), None);
+        assert_eq!(screen.grid().tooltip(at(1, 0)), Some(&tip));
+        screen.apply(&Move::new(Movement::ToBeginning).into());
+        screen.apply(&InsertRows::new(2, true).into());
+        assert_eq!(screen.grid().tooltip(at(1, 2)), Some(&tip));
+        screen.apply(&Move::new(Movement::Row(2)).into());
+        screen.apply(&InsertBlank::new(2).into());
+        assert_eq!(screen.grid().tooltip(at(3, 2)), Some(&tip));
+        screen.apply(&RemoveChars::new(1).into());
+        assert_eq!(screen.grid().tooltip(at(2, 2)), Some(&tip));
+        screen.apply(&Erase::new(Area::WholeScreen).into());
+        assert_eq!(screen.grid().tooltip(at(2, 2)), None);
+
+        // Scrolling a tooltip off of the grid removes it.
+        screen.apply(&AddToolTip(at(0, 0), String::from("tip")).into());
+        screen.write("\n\n\nx");
+        assert!((0..3).all(|y| (0..4).all(|x| screen.grid().tooltip(at(x, y)).is_none())));
+    }
+
+    #[test]
+    fn hyperlinks() {
+        let mut screen = Screen::new(4, 3);
+        let link = Hyperlink { id: Some(1), url: String::from("https://example.com") };
+        screen.write("a");
+        screen.apply(&BeginHyperlink::new(link.id, link.url.clone()).into());
+        screen.write("bcde");
+        screen.apply(&EndHyperlink.into());
+        screen.write("f");
+        assert_eq!(screen.hyperlink(), None);
+        let links: Vec<_> = [at(0, 0), at(1, 0), at(3, 0), at(0, 1), at(1, 1)].iter()
+                                .map(|&coords| screen.grid().hyperlink(coords)).collect();
+        assert_eq!(links, [None, Some(&link), Some(&link), Some(&link), None]);
+
+        // Writing over linked text without a link removes it.
+        screen.apply(&Move::new(Movement::Position(at(1, 0))).into());
+        screen.write("x");
+        assert_eq!(screen.grid().hyperlink(at(1, 0)), None);
+
+        screen.apply(&AddHyperlink(Area::CursorRow, String::from("file:///log")).into());
+        let file = Hyperlink { id: None, url: String::from("file:///log") };
+        assert_eq!(screen.grid().hyperlink(at(3, 0)), Some(&file));
+        assert_eq!(screen.grid().hyperlink(at(0, 1)), Some(&link));
+
+        // Links move with the content when the grid is resized.
+        screen.resize(3, 3, ResizeRule::MaxRightBottom);
+        assert_eq!(screen.grid().hyperlink(at(2, 0)), Some(&file));
+        assert_eq!(screen.grid().hyperlink(at(0, 1)), None);
+    }
+
+    #[test]
+    fn links_move_with_their_cells() {
+        let link = Hyperlink { id: None, url: String::from("https://example.com") };
+        let linked = |screen: &Screen| -> Vec<Coords> {
+            screen.grid().cells().iter().filter(|&(_, cell)| cell.link.is_some())
+                  .map(|(coords, _)| coords).collect()
+        };
+
+        // Text which scrolls off of the grid takes its link with it.
+        let mut screen = Screen::new(4, 2);
+        screen.apply(&BeginHyperlink::new(None, link.url.clone()).into());
+        screen.write("ab");
+        screen.apply(&EndHyperlink.into());
+        screen.write("\n\nxy");
+        assert_eq!(rows(&screen), ["", "xy"]);
+        assert_eq!(linked(&screen), []);
+
+        let mut screen = Screen::new(4, 3);
+        screen.apply(&Move::new(Movement::Position(at(1, 1))).into());
+        screen.apply(&BeginHyperlink::new(None, link.url.clone()).into());
+        screen.write("ab");
+        assert_eq!(screen.grid().hyperlink(at(2, 1)), Some(&link));
+        screen.apply(&ScrollScreen::new(Direction::Up, 1).into());
+        assert_eq!(linked(&screen), [at(1, 0), at(2, 0)]);
+        screen.apply(&Move::new(Movement::ToBeginning).into());
+        screen.apply(&InsertRows::new(1, true).into());
+        assert_eq!(linked(&screen), [at(1, 1), at(2, 1)]);
+        screen.apply(&Move::new(Movement::Row(1)).into());
+        screen.apply(&InsertBlank::new(1).into());
+        assert_eq!(linked(&screen), [at(2, 1), at(3, 1)]);
+        screen.apply(&RemoveChars::new(3).into());
+        assert_eq!(linked(&screen), [at(0, 1)]);
+        screen.apply(&RemoveRows::new(1, true).into());
+        assert_eq!(linked(&screen), []);
+
+        screen.apply(&AddHyperlink(Area::WholeScreen, link.url.clone()).into());
+        assert_eq!(linked(&screen).len(), 12);
+        screen.apply(&Erase::new(Area::WholeScreen).into());
+        assert_eq!(linked(&screen), []);
+    }
+
+    #[test]
+    fn panels() {
+        let mut screen = screen(4, 2, &["abcd"]);
+        screen.apply(&PushPanel(None, None).into());
+        assert_eq!(rows(&screen), ["", ""]);
+        screen.write("x");
+        screen.apply(&RotateSectionUp(None).into());
+        assert_eq!(rows(&screen), ["abcd", ""]);
+        screen.apply(&RotateSectionDown(None).into());
+        assert_eq!(rows(&screen), ["x", ""]);
+        screen.apply(&PopPanel(None).into());
+        screen.apply(&PopPanel(None).into());
+        assert_eq!(rows(&screen), ["abcd", ""]);
+    }
+
+    #[test]
+    fn splitting_panels() {
+        let mut screen = screen(6, 4, &["abcdef", "ghijkl", "mnopqr"]);
+        let split = SplitPanel::new(1, 2, SplitKind::Horizontal(2), Some(SaveGrid::Right),
+                                    Some(ResizeRule::MaxRightBottom), None, None);
+        screen.apply(&split.clone().into());
+        assert_eq!(screen.layout().active(), 2);
+        assert_eq!(screen.layout().region(1), Some(Region::new(0, 0, 6, 2)));
+        assert_eq!(screen.layout().region(2), Some(Region::new(0, 2, 6, 4)));
+        assert_eq!(rows(&screen), ["mnopqr", ""]);
+        assert_eq!(screen.section_grid(1).unwrap().text(0), "");
+        assert_eq!(screen.section_grid(0), None);
+
+        // Splitting into tags which are already in use does nothing.
+        screen.apply(&SplitPanel::new(2, 3, SplitKind::Vertical(3), None, None, Some(1),
+                                      None).into());
+        assert_eq!(screen.layout().region(3), None);
+
+        screen.resize(6, 8, ResizeRule::MaxLeftTop);
+        assert_eq!(screen.layout().region(2), Some(Region::new(0, 6, 6, 8)));
+        assert_eq!(rows(&screen), ["mnopqr", ""]);
+
+        screen.apply(&SwitchActiveSection(1).into());
+        screen.write("top");
+        screen.apply(&UnsplitPanel::new(SaveGrid::Left, 0).into());
+        assert_eq!(screen.layout().active(), 0);
+        assert_eq!(screen.layout().region(1), None);
+        assert_eq!(rows(&screen), ["top", "", "", "", "", "", "", ""]);
+    }
+}